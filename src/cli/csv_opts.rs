@@ -1,42 +1,678 @@
+use std::str::FromStr;
+
+use chrono::NaiveDate;
 use strum::{Display, EnumString, IntoStaticStr};
 
 use super::prelude::*;
+use crate::{
+    ComputedColumn, CsvConvertOptions, DateOptions, Melt, Pivot, SampleMode, XmlOptions,
+    check_date_format,
+};
+const DEFAULT_INPUT: &str = "./assets/juventus.csv";
+
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct CsvOpts {
-    #[arg(short, long, help = "Input file path",value_parser = verify_file,default_value = "./assets/juventus.csv")]
-    pub input: String,
+    #[command(subcommand)]
+    pub sub: Option<CsvSubCommand>,
 
-    #[arg(short, long, help = "Output file path")]
+    // No clap default: clap validates defaults even when a subcommand runs instead
+    #[arg(
+        short,
+        long,
+        help = "Input file path or glob pattern [default: ./assets/juventus.csv]",
+        value_parser = verify_input
+    )]
+    pub input: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Output file path, may use {stem}, {name}, {dir} and {format}"
+    )]
     pub output: Option<String>,
 
     #[arg(short, long, help = "Output format", default_value = "json",value_parser = parse_format)]
     pub format: OutputFormat,
 
+    #[arg(long, help = "Keep converting the remaining files after a failure")]
+    pub keep_going: bool,
+
     #[arg(short, long, help = "Convert to JSON format", default_value_t = ',')]
     pub delimiter: char,
 
     #[arg(long, help = "CSV has header", default_value_t = true)]
     pub header: bool,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+
+    #[command(flatten)]
+    pub date: DateArgs,
+
+    #[arg(
+        long = "add-col",
+        help = "Computed column as NAME=EXPR, e.g. \"label=Name + ' #' + Kit Number\"",
+        value_parser = parse_computed_column
+    )]
+    pub add_cols: Vec<ComputedColumn>,
+
+    #[arg(
+        short,
+        long,
+        help = "Worker threads, 0 uses every core",
+        default_value_t = 0
+    )]
+    pub jobs: usize,
+
+    #[arg(long, help = "Escape cells starting with = + - @ in csv output")]
+    pub sanitize_formulas: bool,
+}
+
+#[derive(Parser, Debug)]
+pub enum CsvSubCommand {
+    #[command(name = "from-xml", about = "Convert XML rows to JSON, CSV or YAML")]
+    FromXml(CsvFromXmlOpts),
+    #[command(about = "Mask or pseudonymize columns before sharing a csv file")]
+    Mask(CsvMaskOpts),
+    #[command(about = "Generate fake rows from a YAML or JSON schema")]
+    Fake(CsvFakeOpts),
+    #[command(about = "Reshape long data into wide form")]
+    Pivot(CsvPivotOpts),
+    #[command(about = "Unpivot wide data into long form")]
+    Melt(CsvMeltOpts),
+    #[command(
+        name = "infer-schema",
+        about = "Infer column types from a sample of rows"
+    )]
+    InferSchema(CsvInferSchemaOpts),
+    #[command(about = "Random, stratified, head or tail sample of rows")]
+    Sample(CsvSampleOpts),
+    #[command(about = "Build a sidecar index of record offsets")]
+    Index(CsvIndexOpts),
+    #[command(about = "Count records, using the index when present")]
+    Count(CsvIndexOpts),
+    #[command(about = "Extract a range of rows, using the index when present")]
+    Slice(CsvSliceOpts),
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvFromXmlOpts {
+    #[arg(short, long, help = "Input file path", value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, help = "Output file path")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output format", default_value = "json",value_parser = parse_format)]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvMaskOpts {
+    #[arg(short, long, help = "Input file path", value_parser = verify_file, default_value = "./assets/juventus.csv")]
+    pub input: String,
+
+    #[arg(short, long, help = "Output file path", default_value = "masked.csv")]
+    pub output: String,
+
+    #[arg(
+        long = "col",
+        help = "Column mask as NAME=STRATEGY, strategy is one of hash, year-only, redact",
+        value_parser = parse_mask_rule,
+        required = true
+    )]
+    pub cols: Vec<MaskRule>,
+
+    #[arg(short, long, help = "Blake3 key file for the hash strategy", value_parser = verify_file)]
+    pub key: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvFakeOpts {
+    #[arg(short, long, help = "Schema file path", value_parser = verify_file)]
+    pub schema: String,
+
+    #[arg(short, long, help = "Number of rows", default_value_t = 100)]
+    pub rows: usize,
+
+    #[arg(long, help = "Random seed for reproducible output")]
+    pub seed: Option<u64>,
+
+    #[arg(long, help = "Reference date for ages as YYYY-MM-DD, defaults to the current date", value_parser = parse_iso_date)]
+    pub today: Option<NaiveDate>,
+
+    #[arg(short, long, help = "Output file path")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output format", default_value = "csv",value_parser = parse_format)]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvPivotOpts {
+    #[arg(short, long, help = "Input file path", value_parser = verify_file, default_value = "./assets/juventus.csv")]
+    pub input: String,
+
+    #[arg(short, long, help = "Output file path")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output format", default_value = "csv",value_parser = parse_format)]
+    pub format: OutputFormat,
+
+    #[arg(
+        long,
+        help = "Columns identifying an output row",
+        value_delimiter = ',',
+        required = true
+    )]
+    pub index: Vec<String>,
+
+    #[arg(long, help = "Column whose values become output columns")]
+    pub columns: String,
+
+    #[arg(long, help = "Column aggregated into each cell")]
+    pub values: Option<String>,
+
+    #[arg(long, help = "Aggregation: count, sum, mean, min, max, first", default_value = "count", value_parser = parse_aggregation)]
+    pub agg: Aggregation,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvMeltOpts {
+    #[arg(short, long, help = "Input file path", value_parser = verify_file, default_value = "./assets/juventus.csv")]
+    pub input: String,
+
+    #[arg(short, long, help = "Output file path")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output format", default_value = "csv",value_parser = parse_format)]
+    pub format: OutputFormat,
+
+    #[arg(
+        long,
+        help = "Columns kept on every output row",
+        value_delimiter = ',',
+        required = true
+    )]
+    pub id: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Columns to unpivot, defaults to all other columns",
+        value_delimiter = ','
+    )]
+    pub vars: Vec<String>,
+
+    #[arg(long, help = "Name of the variable column", default_value = "variable")]
+    pub var_name: String,
+
+    #[arg(long, help = "Name of the value column", default_value = "value")]
+    pub value_name: String,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvInferSchemaOpts {
+    #[arg(help = "Input file path", value_parser = verify_file, default_value = "./assets/juventus.csv")]
+    pub input: String,
+
+    #[arg(short, long, help = "Output file path, stdout when omitted")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Schema format: yaml, json-schema, rust", default_value = "yaml", value_parser = parse_schema_format)]
+    pub format: SchemaFormat,
+
+    #[arg(long, help = "Number of rows to sample", default_value_t = 1000)]
+    pub sample: usize,
+
+    #[arg(
+        long,
+        help = "Most distinct values reported as enum candidates",
+        default_value_t = 10
+    )]
+    pub max_enum: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvSampleOpts {
+    #[arg(short, long, help = "Input file path", value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, help = "Output file path")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output format", default_value = "csv",value_parser = parse_format)]
+    pub format: OutputFormat,
+
+    #[arg(
+        short,
+        long = "n",
+        help = "Number of rows to sample",
+        default_value_t = 10
+    )]
+    pub n: usize,
+
+    #[arg(long, help = "Random seed for a reproducible sample")]
+    pub seed: Option<u64>,
+
+    #[arg(long, help = "Keep the share of each value of this column")]
+    pub stratify: Option<String>,
+
+    #[arg(long, help = "Take the first N rows", conflicts_with_all = ["stratify", "tail"])]
+    pub head: Option<usize>,
+
+    #[arg(long, help = "Take the last N rows", conflicts_with = "stratify")]
+    pub tail: Option<usize>,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+}
+
+impl CsvSampleOpts {
+    fn mode(&self) -> SampleMode {
+        match (self.head, self.tail, &self.stratify) {
+            (Some(n), ..) => SampleMode::Head(n),
+            (_, Some(n), _) => SampleMode::Tail(n),
+            (.., Some(column)) => SampleMode::Stratified {
+                n: self.n,
+                column: column.clone(),
+            },
+            _ => SampleMode::Random { n: self.n },
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvIndexOpts {
+    #[arg(help = "Input file path", value_parser = verify_file)]
+    pub input: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct CsvSliceOpts {
+    #[arg(help = "Input file path", value_parser = verify_file)]
+    pub input: String,
+
+    #[arg(long, help = "Row range START..END, 0 based and END exclusive", value_parser = parse_row_range)]
+    pub rows: RowRange,
+
+    #[arg(short, long, help = "Output file path")]
+    pub output: Option<String>,
+
+    #[arg(short, long, help = "Output format", default_value = "csv",value_parser = parse_format)]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub xml: XmlArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+fn parse_row_range(s: &str) -> Result<RowRange, anyhow::Error> {
+    s.parse()
+}
+
+impl FromStr for RowRange {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| anyhow::anyhow!("Invalid row range, expected START..END"))?;
+        let start = match start.trim() {
+            "" => 0,
+            n => n.parse()?,
+        };
+        let end = match end.trim() {
+            "" => None,
+            n => Some(n.parse()?),
+        };
+        if let Some(end) = end {
+            anyhow::ensure!(start <= end, "Row range start is after its end");
+        }
+        Ok(Self { start, end })
+    }
+}
+
+#[derive(Debug, Clone, Copy, EnumString, Display, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SchemaFormat {
+    Yaml,
+    JsonSchema,
+    /// a serde struct like `Record`
+    Rust,
+}
+
+fn parse_schema_format(s: &str) -> Result<SchemaFormat, anyhow::Error> {
+    s.parse()
+        .map_err(|e| anyhow::anyhow!("Invalid schema format {}: {}", s, e))
+}
+
+#[derive(Debug, Clone, Copy, EnumString, Display, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum Aggregation {
+    Count,
+    Sum,
+    Mean,
+    Min,
+    Max,
+    First,
+}
+
+fn parse_aggregation(s: &str) -> Result<Aggregation, anyhow::Error> {
+    s.parse()
+        .map_err(|e| anyhow::anyhow!("Invalid aggregation {}: {}", s, e))
+}
+
+#[derive(Debug, Clone)]
+pub struct MaskRule {
+    pub column: String,
+    pub strategy: MaskStrategy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum MaskStrategy {
+    /// keyed blake3 pseudonym
+    Hash,
+    /// keep only the year of a date
+    YearOnly,
+    Redact,
+}
+
+fn parse_date_format(s: &str) -> Result<String, anyhow::Error> {
+    check_date_format(s)?;
+    Ok(s.to_string())
+}
+
+fn parse_iso_date(s: &str) -> Result<NaiveDate, anyhow::Error> {
+    Ok(NaiveDate::parse_from_str(s, "%Y-%m-%d")?)
+}
+
+fn parse_computed_column(s: &str) -> Result<ComputedColumn, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_mask_rule(s: &str) -> Result<MaskRule, anyhow::Error> {
+    s.parse()
+}
+
+impl FromStr for MaskRule {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, strategy) = s
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid mask rule, expected NAME=STRATEGY"))?;
+        let strategy = strategy
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid mask strategy {}: {}", strategy, e))?;
+        Ok(Self {
+            column: column.to_string(),
+            strategy,
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct XmlArgs {
+    #[arg(long, help = "XML root element name", default_value = "rows")]
+    pub xml_root: String,
+
+    #[arg(long, help = "XML row element name", default_value = "row")]
+    pub xml_row: String,
+
+    #[arg(long, help = "Write XML columns as attributes of the row element")]
+    pub xml_attrs: bool,
+}
+
+impl From<XmlArgs> for XmlOptions {
+    fn from(args: XmlArgs) -> Self {
+        Self {
+            root: args.xml_root,
+            row: args.xml_row,
+            attributes: args.xml_attrs,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DateArgs {
+    #[arg(long = "date-col", help = "Date column to normalize, may be repeated")]
+    pub date_cols: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Input date format (chrono syntax)",
+        default_value = "%b %d, %Y"
+    )]
+    pub date_in: String,
+
+    #[arg(
+        long,
+        help = "Output date format (chrono syntax or iso8601)",
+        default_value = "iso8601",
+        value_parser = parse_date_format
+    )]
+    pub date_out: String,
+
+    #[arg(
+        long,
+        help = "Add a column with the current age computed from the first date column",
+        requires = "date_cols"
+    )]
+    pub age_col: Option<String>,
+}
+
+impl From<DateArgs> for DateOptions {
+    fn from(args: DateArgs) -> Self {
+        Self {
+            columns: args.date_cols,
+            input_format: args.date_in,
+            output_format: args.date_out,
+            age_column: args.age_col,
+        }
+    }
 }
 
 impl CmdExc for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let output = self
-            .output
-            .unwrap_or_else(|| format!("output.{}", self.format));
-        let _ = crate::process_csv(&self.input, &output, self.format);
+        if let Some(sub) = self.sub {
+            return sub.execute().await;
+        }
+        let opts = CsvConvertOptions {
+            xml: self.xml.into(),
+            dates: self.date.into(),
+            computed: self.add_cols,
+            jobs: self.jobs,
+            sanitize_formulas: self.sanitize_formulas,
+        };
+        let input = match self.input {
+            Some(input) => input,
+            None => {
+                anyhow::ensure!(
+                    std::path::Path::new(DEFAULT_INPUT).exists(),
+                    "No --input given and the default {} does not exist",
+                    DEFAULT_INPUT
+                );
+                DEFAULT_INPUT.to_string()
+            }
+        };
+        if !crate::is_glob(&input) {
+            let output = self
+                .output
+                .unwrap_or_else(|| format!("output.{}", self.format));
+            let stats = crate::process_csv(&input, &output, self.format, &opts)?;
+            if opts.sanitize_formulas {
+                eprintln!("Sanitized {} cells", stats.sanitized);
+            }
+            return Ok(());
+        }
+
+        let inputs = crate::expand_inputs(&input)?;
+        let template = self.output.as_deref().unwrap_or("{stem}.{format}");
+        let summary =
+            crate::process_csv_batch(&inputs, template, self.format, &opts, self.keep_going)?;
+        eprintln!(
+            "Converted {} of {} files, {} failed",
+            summary.succeeded.len(),
+            inputs.len(),
+            summary.failed.len()
+        );
+        for (input, e) in &summary.failed {
+            eprintln!("  {}: {:#}", input.display(), e);
+        }
+        if !summary.failed.is_empty() {
+            anyhow::bail!("{} files failed to convert", summary.failed.len());
+        }
         Ok(())
     }
 }
+
+impl CmdExc for CsvSubCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            CsvSubCommand::FromXml(opts) => {
+                let output = opts
+                    .output
+                    .unwrap_or_else(|| format!("output.{}", opts.format));
+                let mut reader = crate::get_reader(&opts.input)?;
+                crate::process_xml(&mut reader, &output, opts.format, &opts.xml.into())
+            }
+            CsvSubCommand::Mask(opts) => {
+                let key = opts.key.as_deref().map(crate::read_input).transpose()?;
+                let key = key.as_deref().map(crate::trim_whitespace);
+                crate::process_csv_mask(&opts.input, &opts.output, &opts.cols, key)
+            }
+            CsvSubCommand::Fake(opts) => {
+                let output = opts
+                    .output
+                    .unwrap_or_else(|| format!("output.{}", opts.format));
+                let schema = String::from_utf8(crate::read_input(&opts.schema)?)?;
+                crate::process_csv_fake(
+                    &schema,
+                    opts.rows,
+                    opts.seed,
+                    opts.today,
+                    &output,
+                    opts.format,
+                    &opts.xml.into(),
+                )
+            }
+            CsvSubCommand::Pivot(opts) => {
+                let output = opts
+                    .output
+                    .unwrap_or_else(|| format!("output.{}", opts.format));
+                let pivot = Pivot {
+                    index: opts.index,
+                    columns: opts.columns,
+                    values: opts.values,
+                    agg: opts.agg,
+                };
+                crate::process_csv_pivot(
+                    &opts.input,
+                    &output,
+                    opts.format,
+                    &pivot,
+                    &opts.xml.into(),
+                )
+            }
+            CsvSubCommand::InferSchema(opts) => {
+                let schema = crate::process_csv_infer_schema(
+                    &opts.input,
+                    opts.sample,
+                    opts.max_enum,
+                    opts.format,
+                )?;
+                match opts.output {
+                    Some(output) => std::fs::write(output, schema)?,
+                    None => print!("{}", schema),
+                }
+                Ok(())
+            }
+            CsvSubCommand::Sample(opts) => {
+                let output = opts
+                    .output
+                    .clone()
+                    .unwrap_or_else(|| format!("sample.{}", opts.format));
+                crate::process_csv_sample(
+                    &opts.input,
+                    &output,
+                    opts.format,
+                    &opts.mode(),
+                    opts.seed,
+                    &opts.xml.into(),
+                )
+            }
+            CsvSubCommand::Index(opts) => {
+                let count = crate::process_csv_index(&opts.input)?;
+                eprintln!("Indexed {} records", count);
+                Ok(())
+            }
+            CsvSubCommand::Count(opts) => {
+                println!("{}", crate::process_csv_count(&opts.input)?);
+                Ok(())
+            }
+            CsvSubCommand::Slice(opts) => {
+                let output = opts
+                    .output
+                    .unwrap_or_else(|| format!("slice.{}", opts.format));
+                crate::process_csv_slice(
+                    &opts.input,
+                    &output,
+                    opts.format,
+                    opts.rows.start,
+                    opts.rows.end,
+                    &opts.xml.into(),
+                )
+            }
+            CsvSubCommand::Melt(opts) => {
+                let output = opts
+                    .output
+                    .unwrap_or_else(|| format!("output.{}", opts.format));
+                let melt = Melt {
+                    id: opts.id,
+                    vars: opts.vars,
+                    var_name: opts.var_name,
+                    value_name: opts.value_name,
+                };
+                crate::process_csv_melt(&opts.input, &output, opts.format, &melt, &opts.xml.into())
+            }
+        }
+    }
+}
 #[derive(Debug, Clone, Copy, IntoStaticStr, EnumString, Display)]
 #[strum(serialize_all = "lowercase")]
 pub enum OutputFormat {
     Json,
     Csv,
     Yaml,
+    Xml,
+}
+
+/// Accept glob patterns as is, they are expanded at run time
+fn verify_input(input: &str) -> Result<String, &'static str> {
+    match crate::is_glob(input) {
+        true => Ok(input.to_string()),
+        false => verify_file(input),
+    }
 }
 
-fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
+pub(super) fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format
         .parse::<OutputFormat>()
         .map_err(|e| anyhow::anyhow!("Unsupported output format: {}", e))
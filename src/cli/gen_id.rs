@@ -0,0 +1,138 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::rngs::OsRng;
+use strum::{Display, EnumString, IntoStaticStr};
+
+use super::prelude::*;
+use crate::{
+    Monotonic, NANOID_ALPHABET, gen_nanoid, gen_pass_batch, gen_secret, gen_ulid, gen_uuid_v4,
+    gen_uuid_v7,
+};
+
+#[derive(Parser, Debug)]
+pub enum GenSubCommand {
+    #[command(name = "uuid", about = "Random (v4) or time ordered (v7) UUIDs")]
+    Uuid(GenUuidOpts),
+    #[command(name = "ulid", about = "Lexicographically sortable ULIDs")]
+    Ulid(GenUlidOpts),
+    #[command(name = "nanoid", about = "Short URL-safe random ids")]
+    Nanoid(GenNanoidOpts),
+    #[command(name = "secret", about = "Random bytes for API keys and tokens")]
+    Secret(GenSecretOpts),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BatchArgs {
+    #[arg(
+        short,
+        long,
+        help = "Number of values to generate",
+        default_value_t = 1
+    )]
+    pub count: usize,
+    #[arg(long, help = "Never repeat a value within one run")]
+    pub unique: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct GenUuidOpts {
+    #[arg(
+        long,
+        help = "Time ordered version 7 UUID instead of a random version 4"
+    )]
+    pub v7: bool,
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct GenUlidOpts {
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct GenNanoidOpts {
+    #[arg(long, help = "Id length", default_value_t = 21, value_parser = clap::value_parser!(u16).range(1..=4096))]
+    pub len: u16,
+    #[arg(long, help = "Characters to draw from", default_value = NANOID_ALPHABET, allow_hyphen_values = true)]
+    pub alphabet: String,
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct GenSecretOpts {
+    #[arg(long, help = "Number of random bytes", default_value_t = 32, value_parser = clap::value_parser!(u16).range(1..=4096))]
+    pub bytes: u16,
+    #[arg(long, help = "Output encoding: hex, base64url", value_parser = parse_secret_encoding, default_value = "hex")]
+    pub encoding: SecretEncoding,
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+#[derive(Debug, Clone, Copy, EnumString, Display, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum SecretEncoding {
+    Hex,
+    Base64url,
+}
+
+impl CmdExc for GenSubCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut rng = OsRng;
+        // one clock for the whole batch keeps v7 UUIDs and ULIDs in generation order
+        let mut clock = Monotonic::new();
+        let (batch, entropy, mut next): (_, f64, Box<dyn FnMut() -> anyhow::Result<String>>) =
+            match self {
+                GenSubCommand::Uuid(opts) if opts.v7 => (
+                    opts.batch,
+                    74.0,
+                    Box::new(move || {
+                        Ok(gen_uuid_v7(&mut rng, &mut clock, now_millis()?).to_string())
+                    }),
+                ),
+                GenSubCommand::Uuid(opts) => (
+                    opts.batch,
+                    122.0,
+                    Box::new(move || Ok(gen_uuid_v4(&mut rng).to_string())),
+                ),
+                GenSubCommand::Ulid(opts) => (
+                    opts.batch,
+                    80.0,
+                    Box::new(move || Ok(gen_ulid(&mut rng, &mut clock, now_millis()?))),
+                ),
+                GenSubCommand::Nanoid(opts) => {
+                    let symbols = opts
+                        .alphabet
+                        .chars()
+                        .collect::<std::collections::HashSet<_>>();
+                    let entropy = opts.len as f64 * (symbols.len() as f64).log2();
+                    (
+                        opts.batch,
+                        entropy,
+                        Box::new(move || gen_nanoid(&mut rng, opts.len as usize, &opts.alphabet)),
+                    )
+                }
+                GenSubCommand::Secret(opts) => (
+                    opts.batch,
+                    opts.bytes as f64 * 8.0,
+                    Box::new(move || Ok(gen_secret(&mut rng, opts.bytes as usize, opts.encoding))),
+                ),
+            };
+        let values = gen_pass_batch(batch.count, batch.unique, entropy, || Ok((next()?, ())))?;
+        for (value, ()) in values {
+            println!("{}", value);
+        }
+        Ok(())
+    }
+}
+
+fn now_millis() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+fn parse_secret_encoding(s: &str) -> Result<SecretEncoding, anyhow::Error> {
+    s.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid encoding {}, expected hex or base64url", s))
+}
@@ -1,35 +1,604 @@
-use std::rc::Rc;
+use std::io::{IsTerminal, Write};
 
-use super::prelude::*;
+use strum::{Display, EnumString, IntoStaticStr};
+
+use super::{OutputFormat, csv_opts::parse_format, prelude::*};
+use crate::{
+    AMBIGUOUS, CharClass, ClassRule, GenPassError, MAX_LENGTH, PassSpec, PassphraseSpec,
+    PasswordPolicy, PronounceableSpec, PwnedDb, StrengthReport, StrengthTarget, Wordlist,
+    XmlOptions, gen_pass_batch, gen_passphrase, gen_pronounceable, serialize_rows,
+};
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
-    #[arg(short, long, help = "Password length", default_value_t = 16)]
-    pub length: u8,
-    #[arg(long, help = "Password has upper case", default_value_t = true)]
+    #[command(subcommand)]
+    pub sub: Option<GenPassSubCommand>,
+    #[command(flatten)]
+    pub chars: CharsetArgs,
+    #[arg(
+        long,
+        help = "Generate a diceware passphrase instead of a random string",
+        conflicts_with_all = [
+            "policy", "policy_file", "ban",
+            "charset", "classes", "symbols", "exclude", "exclude_ambiguous",
+            "min_upper", "min_lower", "min_digits", "min_symbols"
+        ]
+    )]
+    pub passphrase: bool,
+    #[arg(
+        long,
+        help = "Number of words in the passphrase",
+        default_value_t = 6,
+        requires = "passphrase"
+    )]
+    pub words: usize,
+    #[arg(
+        long,
+        help = "Separator between passphrase words",
+        default_value = "-",
+        requires = "passphrase"
+    )]
+    pub separator: String,
+    #[arg(
+        long,
+        help = "Capitalize each passphrase word",
+        requires = "passphrase"
+    )]
+    pub capitalize: bool,
+    #[arg(
+        long,
+        help = "Append a random digit to one passphrase word",
+        requires = "passphrase"
+    )]
+    pub add_digit: bool,
+    #[arg(long, help = "Wordlist file, one word per line (diceware numbering is ignored)", value_parser = verify_file, requires = "passphrase")]
+    pub wordlist: Option<String>,
+    #[arg(
+        long,
+        help = "Generate consonant-vowel syllables that are easy to read out; --min-upper, --min-digits and --min-symbols [default: 0] add capitals, digits and symbols",
+        conflicts_with_all = [
+            "passphrase", "charset", "classes", "min_lower",
+            "no_upper", "no_lower", "no_number", "no_symbol"
+        ]
+    )]
+    pub pronounceable: bool,
+    #[arg(long, help = "Regenerate until the zxcvbn score is at least this (0-4)", value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: Option<u8>,
+    #[arg(
+        long,
+        help = "Refuse settings that give fewer bits of entropy than this"
+    )]
+    pub min_entropy: Option<f64>,
+    #[command(flatten)]
+    pub policy: PolicyArgs,
+    #[arg(
+        long,
+        help = "Regenerate any password found in the --pwned-db breach list",
+        requires = "pwned_db"
+    )]
+    pub check_breached: bool,
+    #[arg(long, help = "pwned-passwords-sha1-ordered-by-hash file", value_parser = verify_file)]
+    pub pwned_db: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "Number of passwords to generate",
+        default_value_t = 1
+    )]
+    pub count: usize,
+    #[arg(long, help = "Never repeat a password within one run")]
+    pub unique: bool,
+    #[arg(long, help = "List format: json, csv, yaml, xml [default: one password per line]", value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+    #[arg(long, help = "Add each password's zxcvbn score to the list")]
+    pub with_score: bool,
+    #[arg(
+        short,
+        long,
+        help = "Write the passwords to this file instead of stdout"
+    )]
+    pub output: Option<String>,
+    #[arg(short, long, help = "Print the full strength report to stderr")]
+    pub verbose: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["verbose", "count", "format", "output"],
+        help = "Print the password and its strength report as JSON"
+    )]
+    pub json: bool,
+}
+
+/// Length and alphabet options shared by generation and derivation.
+#[derive(clap::Args, Debug)]
+pub struct CharsetArgs {
+    #[arg(
+        short,
+        long,
+        help = "Password length, up to 4096 [default: 16]",
+        value_parser = clap::value_parser!(u16).range(1..=MAX_LENGTH as i64)
+    )]
+    pub length: Option<u16>,
+    // The hidden --upper-case, --lower-case, --number and --symbol flags are the old
+    // always-on switches. Their values are never read: they keep old scripts parsing,
+    // and through `overrides_with` a later `--upper-case` cancels an earlier `--no-upper`.
+    #[arg(long, hide = true, overrides_with = "no_upper")]
     pub upper_case: bool,
-    #[arg(long, help = "Password has lower case", default_value_t = true)]
+    #[arg(long, help = "Leave out upper case letters")]
+    pub no_upper: bool,
+    #[arg(long, hide = true, overrides_with = "no_lower")]
     pub lower_case: bool,
-    #[arg(long, help = "Password has number", default_value_t = true)]
+    #[arg(long, help = "Leave out lower case letters")]
+    pub no_lower: bool,
+    #[arg(long, hide = true, overrides_with = "no_number")]
     pub number: bool,
-    #[arg(long, help = "Password has symbol", default_value_t = true)]
+    #[arg(long, help = "Leave out digits")]
+    pub no_number: bool,
+    #[arg(long, hide = true, overrides_with = "no_symbol")]
     pub symbol: bool,
+    #[arg(long, help = "Leave out symbols")]
+    pub no_symbol: bool,
+    #[arg(
+        long,
+        help = "Enabled classes, e.g. 'uln': u=upper, l=lower, n=number, s=symbol",
+        value_parser = parse_classes,
+        conflicts_with_all = ["no_upper", "no_lower", "no_number", "no_symbol"]
+    )]
+    // Spelled out so clap's derive does not see `Vec<T>` and treat --classes as a
+    // repeated flag; `parse_classes` turns one value such as `uln` into the whole list.
+    pub classes: Option<::std::vec::Vec<CharClass>>,
+    #[arg(long, help = "Minimum upper case letters [default: 1]")]
+    pub min_upper: Option<usize>,
+    #[arg(long, help = "Minimum lower case letters [default: 1]")]
+    pub min_lower: Option<usize>,
+    #[arg(long, help = "Minimum digits [default: 1]")]
+    pub min_digits: Option<usize>,
+    #[arg(long, help = "Minimum symbols [default: 1]")]
+    pub min_symbols: Option<usize>,
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Draw from this alphabet instead of the character classes",
+        conflicts_with_all = [
+            "classes", "no_upper", "no_lower", "no_number", "no_symbol",
+            "min_upper", "min_lower", "min_digits", "min_symbols", "symbols"
+        ]
+    )]
+    pub charset: Option<String>,
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Symbol set to use instead of the default !@#$%^&*_"
+    )]
+    pub symbols: Option<String>,
+    #[arg(
+        long,
+        help = "Leave out easily confused characters such as O, 0, I, l and 1"
+    )]
+    pub exclude_ambiguous: bool,
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Characters that must never appear in the password"
+    )]
+    pub exclude: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub enum GenPassSubCommand {
+    #[command(
+        visible_alias = "audit",
+        about = "Audit existing passwords for strength and policy violations"
+    )]
+    Check(GenPassCheckOpts),
+    #[command(about = "Look passwords up in a local Have I Been Pwned SHA-1 list")]
+    Pwned(GenPassPwnedOpts),
+    #[command(about = "Derive a reproducible site password from a master secret")]
+    Derive(GenPassDeriveOpts),
+}
+
+#[derive(Parser, Debug)]
+pub struct GenPassDeriveOpts {
+    #[arg(long, help = "Site or service name, e.g. example.com")]
+    pub site: String,
+    #[arg(long, help = "Login or username at the site", default_value = "")]
+    pub login: String,
+    #[arg(long, help = "Bump to rotate the password", default_value_t = 1)]
+    pub counter: u32,
+    #[arg(long, help = "Read the master secret from this file instead of prompting", value_parser = verify_file)]
+    pub key_file: Option<String>,
+    #[command(flatten)]
+    pub chars: CharsetArgs,
+    #[command(flatten)]
+    pub policy: PolicyArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct GenPassPwnedOpts {
+    #[arg(short, long, help = "File with one password per line; from a terminal, stdin prompts for one password without echo", value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(long, help = "pwned-passwords-sha1-ordered-by-hash file", value_parser = verify_file)]
+    pub db: String,
+    #[arg(long, help = "Show passwords in the report instead of masking them")]
+    pub reveal: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct GenPassCheckOpts {
+    #[arg(short, long, help = "File with one password per line; from a terminal, stdin prompts for one password without echo", value_parser = verify_file, default_value = "-")]
+    pub input: String,
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        help = "Context words such as usernames or service names that make a password guessable"
+    )]
+    pub user_input: Vec<String>,
+    #[command(flatten)]
+    pub policy: PolicyArgs,
+    #[arg(long, help = "Report format: table, json", default_value = "table", value_parser = parse_audit_format)]
+    pub format: AuditFormat,
+    #[arg(long, help = "Show passwords in the report instead of masking them")]
+    pub reveal: bool,
+}
+
+#[derive(Debug, Clone, Copy, EnumString, Display, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum AuditFormat {
+    Table,
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct PolicyArgs {
+    #[arg(long, help = "Policy preset: nist, pci, legacy-8", value_parser = parse_policy)]
+    pub policy: Option<PolicyPreset>,
+    #[arg(long, help = "YAML policy file", value_parser = verify_file, conflicts_with = "policy")]
+    pub policy_file: Option<String>,
+    #[arg(
+        long,
+        help = "Substring the password must not contain, e.g. a username"
+    )]
+    pub ban: Vec<String>,
+}
+
+impl PolicyArgs {
+    pub fn load(&self) -> anyhow::Result<Option<PasswordPolicy>> {
+        let mut policy = match (&self.policy, &self.policy_file) {
+            (Some(preset), _) => preset.policy(),
+            (None, Some(path)) => PasswordPolicy::load(path)?,
+            (None, None) if self.ban.is_empty() => return Ok(None),
+            (None, None) => PasswordPolicy::default(),
+        };
+        policy.banned.extend(self.ban.iter().cloned());
+        Ok(Some(policy))
+    }
+}
+
+#[derive(Debug, Clone, Copy, EnumString, Display, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum PolicyPreset {
+    Nist,
+    Pci,
+    #[strum(serialize = "legacy-8")]
+    Legacy8,
+}
+
+impl CmdExc for GenPassSubCommand {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            GenPassSubCommand::Check(opts) => {
+                let policy = opts.policy.load()?;
+                let user_inputs: Vec<&str> = opts.user_input.iter().map(String::as_str).collect();
+                let mut reader = password_reader(&opts.input)?;
+                let entries = crate::process_password_audit(
+                    &mut reader,
+                    &user_inputs,
+                    policy.as_ref(),
+                    opts.reveal,
+                )?;
+                match opts.format {
+                    AuditFormat::Table => print!("{}", crate::render_audit_table(&entries)),
+                    AuditFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+                }
+                let failed = entries.iter().filter(|e| !e.violations.is_empty()).count();
+                if failed > 0 {
+                    anyhow::bail!("{} of {} passwords break the policy", failed, entries.len());
+                }
+                Ok(())
+            }
+            GenPassSubCommand::Derive(opts) => {
+                let secret = match &opts.key_file {
+                    // a trailing newline from an editor must not change every password
+                    Some(path) => {
+                        let mut secret = std::fs::read(path)?;
+                        while secret.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                            secret.pop();
+                        }
+                        secret
+                    }
+                    None => rpassword::prompt_password("Master secret: ")?.into_bytes(),
+                };
+                // a typo in the secret gives a valid but wrong password, so show what was used
+                eprintln!(
+                    "Secret fingerprint: {}",
+                    crate::secret_fingerprint(&secret)?
+                );
+                let mut spec = opts.chars.spec()?;
+                let policy = opts.policy.load()?;
+                if let Some(policy) = &policy {
+                    policy.apply(&mut spec, opts.chars.length)?;
+                }
+                let password = crate::derive_password(
+                    &secret,
+                    &opts.site,
+                    &opts.login,
+                    opts.counter,
+                    &spec,
+                    policy.as_ref(),
+                )?;
+                println!("{}", password);
+                Ok(())
+            }
+            GenPassSubCommand::Pwned(opts) => {
+                let mut db = PwnedDb::open(&opts.db)?;
+                let mut reader = password_reader(&opts.input)?;
+                let entries = crate::process_password_pwned(&mut reader, &mut db, opts.reveal)?;
+                for e in &entries {
+                    match e.count {
+                        0 => println!("✓ {}: {} not found", e.line, e.password),
+                        n => println!("✗ {}: {} found {} times", e.line, e.password, n),
+                    }
+                }
+                let found = entries.iter().filter(|e| e.count > 0).count();
+                if found > 0 {
+                    anyhow::bail!(
+                        "{} of {} passwords appear in breaches",
+                        found,
+                        entries.len()
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl CmdExc for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let result = crate::gen_pass(
-            self.length,
-            self.upper_case,
-            self.lower_case,
-            self.number,
-            self.symbol,
-        );
-        let strength = zxcvbn::zxcvbn(&result?, &[]);
-        eprintln!("Password score: {:?}", strength.score());
-        let rc = Rc::new(1);
-        println!("{}", rc);
+        if let Some(sub) = self.sub {
+            return sub.execute().await;
+        }
+        let target = StrengthTarget {
+            min_score: self.min_score,
+            min_entropy: self.min_entropy,
+        };
+        let (entropy, mut generate): (f64, Box<dyn FnMut() -> anyhow::Result<String>>) =
+            if self.passphrase {
+                let list = match &self.wordlist {
+                    Some(path) => Wordlist::load(path)?,
+                    None => Wordlist::builtin(),
+                };
+                let spec = self.passphrase_spec();
+                (
+                    spec.entropy_bits(&list),
+                    Box::new(move || gen_passphrase(&list, &spec)),
+                )
+            } else if self.pronounceable {
+                let mut spec = self.chars.pronounceable_spec();
+                let policy = self.policy.load()?;
+                if let Some(policy) = &policy {
+                    spec.apply_policy(policy, self.chars.length)?;
+                }
+                (
+                    spec.entropy_bits(),
+                    Box::new(move || match &policy {
+                        Some(policy) => policy.generate(|| Ok(gen_pronounceable(&spec)?)),
+                        None => Ok(gen_pronounceable(&spec)?),
+                    }),
+                )
+            } else {
+                let mut spec = self.chars.spec()?;
+                match self.policy.load()? {
+                    Some(policy) => {
+                        policy.apply(&mut spec, self.chars.length)?;
+                        (
+                            spec.entropy_bits(),
+                            Box::new(move || policy.generate(|| Ok(crate::gen_pass(&spec)?))),
+                        )
+                    }
+                    None => (
+                        spec.entropy_bits(),
+                        Box::new(move || Ok(crate::gen_pass(&spec)?)),
+                    ),
+                }
+            };
+        if self.check_breached {
+            let mut db = PwnedDb::open(self.pwned_db.as_deref().unwrap_or_default())?;
+            let mut inner = generate;
+            generate = Box::new(move || db.generate(&mut inner));
+        }
+        let mut next = || target.generate(entropy, &mut generate);
+        if self.count > 1 || self.format.is_some() || self.output.is_some() {
+            return self.write_list(gen_pass_batch(self.count, self.unique, entropy, next)?);
+        }
+        let (password, report) = next()?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("{}", password);
+            if self.verbose {
+                eprint!("{}", report);
+            } else {
+                eprintln!(
+                    "Password score: {}, entropy: {:.1} bits",
+                    report.score,
+                    report.entropy_bits.unwrap_or_default()
+                );
+            }
+        }
 
         Ok(())
     }
 }
+
+impl GenPassOpts {
+    fn write_list(&self, passwords: Vec<(String, StrengthReport)>) -> anyhow::Result<()> {
+        let content = match self.format {
+            Some(format) => {
+                let mut headers = vec!["index".to_string(), "password".to_string()];
+                if self.with_score {
+                    headers.push("score".to_string());
+                }
+                let rows: Vec<Vec<String>> = passwords
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (password, report))| {
+                        let mut row = vec![(i + 1).to_string(), password];
+                        if self.with_score {
+                            row.push(report.score.to_string());
+                        }
+                        row
+                    })
+                    .collect();
+                serialize_rows(&headers, &rows, format, &XmlOptions::default())?
+            }
+            None => passwords
+                .into_iter()
+                .map(|(password, report)| match self.with_score {
+                    true => format!("{}\t{}\n", password, report.score),
+                    false => format!("{}\n", password),
+                })
+                .collect::<String>()
+                .into_bytes(),
+        };
+        match &self.output {
+            Some(path) => std::fs::write(path, content)?,
+            None => std::io::stdout().write_all(&content)?,
+        }
+        Ok(())
+    }
+
+    pub fn passphrase_spec(&self) -> PassphraseSpec {
+        PassphraseSpec {
+            words: self.words,
+            separator: self.separator.clone(),
+            capitalize: self.capitalize,
+            add_digit: self.add_digit,
+        }
+    }
+}
+
+impl CharsetArgs {
+    pub fn length(&self) -> u16 {
+        self.length.unwrap_or(16)
+    }
+
+    pub fn spec(&self) -> anyhow::Result<PassSpec> {
+        let mut spec = match &self.charset {
+            Some(charset) => PassSpec {
+                length: self.length(),
+                classes: vec![ClassRule::with_chars(CharClass::Custom, charset, 0)],
+            },
+            None => self.class_spec()?,
+        };
+        if self.exclude_ambiguous {
+            spec.exclude(AMBIGUOUS);
+        }
+        if let Some(exclude) = &self.exclude {
+            spec.exclude(exclude);
+        }
+        Ok(spec)
+    }
+
+    pub fn pronounceable_spec(&self) -> PronounceableSpec {
+        let mut spec = PronounceableSpec::new(self.length());
+        spec.upper = self.min_upper.unwrap_or(0);
+        spec.digits = self.min_digits.unwrap_or(0);
+        spec.symbols = self.min_symbols.unwrap_or(0);
+        if let Some(symbols) = &self.symbols {
+            spec.symbol_chars = symbols.chars().collect();
+            spec.symbol_chars.sort_unstable();
+            spec.symbol_chars.dedup();
+        }
+        if self.exclude_ambiguous {
+            spec.exclude(AMBIGUOUS);
+        }
+        if let Some(exclude) = &self.exclude {
+            spec.exclude(exclude);
+        }
+        spec
+    }
+
+    fn class_spec(&self) -> anyhow::Result<PassSpec> {
+        let enabled = match &self.classes {
+            Some(classes) => classes.clone(),
+            None => CharClass::ALL
+                .into_iter()
+                .filter(|class| !self.disabled(*class))
+                .collect(),
+        };
+        let mut classes = Vec::with_capacity(enabled.len());
+        for class in CharClass::ALL {
+            let min = match class {
+                CharClass::Upper => self.min_upper,
+                CharClass::Lower => self.min_lower,
+                CharClass::Number => self.min_digits,
+                CharClass::Symbol => self.min_symbols,
+                CharClass::Custom => None,
+            };
+            if enabled.contains(&class) {
+                let min = min.unwrap_or(1);
+                classes.push(match (class, &self.symbols) {
+                    (CharClass::Symbol, Some(symbols)) => {
+                        ClassRule::with_chars(class, symbols, min)
+                    }
+                    _ => ClassRule::new(class, min),
+                });
+            } else if min.is_some_and(|m| m > 0) {
+                anyhow::bail!(
+                    "A minimum was set for {} characters, which are disabled",
+                    class.name()
+                );
+            }
+        }
+        Ok(PassSpec {
+            length: self.length(),
+            classes,
+        })
+    }
+
+    fn disabled(&self, class: CharClass) -> bool {
+        match class {
+            CharClass::Upper => self.no_upper,
+            CharClass::Lower => self.no_lower,
+            CharClass::Number => self.no_number,
+            CharClass::Symbol => self.no_symbol,
+            CharClass::Custom => false,
+        }
+    }
+}
+
+fn parse_classes(s: &str) -> Result<Vec<CharClass>, GenPassError> {
+    CharClass::parse_set(s)
+}
+
+fn parse_policy(s: &str) -> Result<PolicyPreset, anyhow::Error> {
+    s.parse()
+        .map_err(|e| anyhow::anyhow!("Unknown policy {}: {}", s, e))
+}
+
+fn parse_audit_format(s: &str) -> Result<AuditFormat, anyhow::Error> {
+    s.parse()
+        .map_err(|e| anyhow::anyhow!("Unsupported report format {}: {}", s, e))
+}
+
+/// Passwords one per line from `input`. On an interactive stdin a single password is
+/// prompted for without echo, so it never lands in shell history or `ps`.
+fn password_reader(input: &str) -> anyhow::Result<Box<dyn std::io::Read>> {
+    if input == "-" && std::io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password: ")?;
+        return Ok(Box::new(std::io::Cursor::new(password)));
+    }
+    crate::get_reader(input)
+}
@@ -1,5 +1,6 @@
 mod base64;
 mod csv_opts;
+mod gen_id;
 mod gen_pass;
 mod http;
 mod prelude;
@@ -8,9 +9,20 @@ use std::path::{Path, PathBuf};
 
 pub use base64::{Base64DecodeOpts, Base64EncodeOpts, Base64Format, Base64SubCommand};
 use clap::Parser;
-pub use csv_opts::{CsvOpts, OutputFormat};
+pub use csv_opts::{
+    Aggregation, CsvFakeOpts, CsvFromXmlOpts, CsvIndexOpts, CsvInferSchemaOpts, CsvMaskOpts,
+    CsvMeltOpts, CsvOpts, CsvPivotOpts, CsvSampleOpts, CsvSliceOpts, CsvSubCommand, DateArgs,
+    MaskRule, MaskStrategy, OutputFormat, RowRange, SchemaFormat, XmlArgs,
+};
 use enum_dispatch::enum_dispatch;
-pub use gen_pass::GenPassOpts;
+pub use gen_id::{
+    BatchArgs, GenNanoidOpts, GenSecretOpts, GenSubCommand, GenUlidOpts, GenUuidOpts,
+    SecretEncoding,
+};
+pub use gen_pass::{
+    AuditFormat, CharsetArgs, GenPassCheckOpts, GenPassDeriveOpts, GenPassOpts, GenPassPwnedOpts,
+    GenPassSubCommand, PolicyArgs, PolicyPreset,
+};
 pub use http::HttpSubCommand;
 pub use text::{TextSignFormat, TextSubCommand};
 #[derive(Parser, Debug)]
@@ -22,11 +34,22 @@ pub struct Opts {
 
 #[derive(Parser, Debug)]
 #[enum_dispatch(CmdExc)]
+#[allow(clippy::large_enum_variant)] // parsed once, boxing buys nothing
 pub enum SubCommand {
     #[command(name = "csv", about = "Show Csv ,or convert Csv to others formats")]
     Csv(CsvOpts),
-    #[command(name = "genpass", about = "Generate password")]
+    #[command(
+        name = "genpass",
+        visible_alias = "password",
+        about = "Generate, audit or derive passwords"
+    )]
     GenPass(GenPassOpts),
+    #[clap(
+        subcommand,
+        name = "gen",
+        about = "Generate UUIDs, ULIDs, nanoids and random secrets"
+    )]
+    Gen(GenSubCommand),
     #[clap(subcommand, name = "base64", about = "Base64 encode or decode")]
     Base64(Base64SubCommand),
     #[clap(subcommand, name = "text", about = "Text sign or verify")]
@@ -69,4 +92,25 @@ mod tests {
         assert_eq!(verify_file("Cargo.toml"), Ok("Cargo.toml".into()));
         assert_eq!(verify_file("not-exist"), Err("Input file does not exist."));
     }
+
+    #[test]
+    fn test_csv_subcommand_skips_default_input() {
+        // the juventus sample is only looked up when converting, so this parses from any
+        // directory
+        let opts = Opts::try_parse_from(["rcli", "csv", "count", "Cargo.toml"]).unwrap();
+        let SubCommand::Csv(csv) = opts.sub else {
+            panic!("expected the csv command");
+        };
+        assert!(csv.input.is_none());
+        assert!(matches!(csv.sub, Some(CsvSubCommand::Count(_))));
+    }
+
+    #[test]
+    fn test_genpass_top_level_args_conflict_with_subcommands() {
+        let derive = ["rcli", "genpass", "derive", "--site", "example.com"];
+        assert!(Opts::try_parse_from(derive).is_ok());
+        let mut args = vec!["rcli", "genpass", "-l", "20"];
+        args.extend(&derive[2..]);
+        assert!(Opts::try_parse_from(args).is_err());
+    }
 }
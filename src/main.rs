@@ -10,12 +10,17 @@ async fn main() -> anyhow::Result<()> {
 // 新增测试
 #[cfg(test)]
 mod tests {
-    use template::{OutputFormat, process_csv};
+    use template::{CsvConvertOptions, OutputFormat, process_csv};
     #[test]
     fn test_process_csv() {
         let input = "test_data/input.csv";
         let output = "test_data/output.json";
-        let result = process_csv(input, output, OutputFormat::Json);
+        let result = process_csv(
+            input,
+            output,
+            OutputFormat::Json,
+            &CsvConvertOptions::default(),
+        );
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,187 @@
+use std::io::{BufRead, BufReader, Read};
+
+use serde::Serialize;
+
+use crate::{PasswordPolicy, StrengthReport, Violation};
+
+/// Strength and policy findings for one candidate password.
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    /// 1-based line of the password in the input.
+    pub line: usize,
+    #[serde(flatten)]
+    pub report: StrengthReport,
+    pub violations: Vec<Violation>,
+}
+
+/// Audit every non-empty line of `reader` as a password.
+///
+/// `user_inputs` are context words (usernames, service names) that zxcvbn treats as
+/// trivially guessable. Unless `reveal` is set, passwords are masked and no weakness or
+/// violation quotes any part of them.
+pub fn process_password_audit(
+    reader: &mut dyn Read,
+    user_inputs: &[&str],
+    policy: Option<&PasswordPolicy>,
+    reveal: bool,
+) -> anyhow::Result<Vec<AuditEntry>> {
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let password = line.trim_end_matches('\r');
+        if password.is_empty() {
+            continue;
+        }
+        let mut entry = match reveal {
+            true => audit_password(password, user_inputs, policy),
+            false => AuditEntry {
+                line: 1,
+                report: StrengthReport::masked(password, user_inputs),
+                violations: policy.map(|p| p.check_masked(password)).unwrap_or_default(),
+            },
+        };
+        entry.line = i + 1;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+pub fn audit_password(
+    password: &str,
+    user_inputs: &[&str],
+    policy: Option<&PasswordPolicy>,
+) -> AuditEntry {
+    AuditEntry {
+        line: 1,
+        report: StrengthReport::new(password, user_inputs),
+        violations: policy.map(|p| p.check(password)).unwrap_or_default(),
+    }
+}
+
+/// Keep the first and last character so reviewers can tell entries apart.
+pub fn mask_password(password: &str) -> String {
+    let chars: Vec<char> = password.chars().collect();
+    match chars.len() {
+        0..=2 => "*".repeat(chars.len()),
+        n => format!("{}{}{}", chars[0], "*".repeat(n - 2), chars[n - 1]),
+    }
+}
+
+/// Render entries as an aligned text table.
+pub fn render_audit_table(entries: &[AuditEntry]) -> String {
+    let headers = ["LINE", "PASSWORD", "SCORE", "WEAKNESSES", "SUGGESTIONS"];
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|e| {
+            let issues: Vec<String> = e
+                .report
+                .weaknesses
+                .iter()
+                .cloned()
+                .chain(e.report.warning.clone())
+                .chain(e.violations.iter().map(|v| v.to_string()))
+                .collect();
+            [
+                e.line.to_string(),
+                e.report.password.clone(),
+                format!("{}/4", e.report.score),
+                dash_if_empty(issues.join("; ")),
+                dash_if_empty(e.report.suggestions.join(" ")),
+            ]
+        })
+        .collect();
+    let mut widths = headers.map(str::len);
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in std::iter::once(headers.map(str::to_string)).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{:<w$}", cell, w = w))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn dash_if_empty(s: String) -> String {
+    if s.is_empty() { "-".to_string() } else { s }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_password() {
+        let input = "alice2024!\n\nCorrect-Horse-Battery-Staple-42\n";
+        let policy = PasswordPolicy {
+            min_length: 12,
+            ..Default::default()
+        };
+        let entries =
+            process_password_audit(&mut input.as_bytes(), &["alice"], Some(&policy), false)
+                .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, 1);
+        assert_eq!(entries[1].line, 3);
+        assert_eq!(entries[0].report.password, "a********!");
+        assert!(
+            entries[0]
+                .report
+                .weaknesses
+                .iter()
+                .any(|w| w.starts_with("context word (5 characters)"))
+        );
+        assert_eq!(entries[0].violations.len(), 1);
+        assert!(entries[1].violations.is_empty());
+
+        let table = render_audit_table(&entries);
+        assert!(table.starts_with("LINE"));
+        assert_eq!(table.lines().count(), 3);
+
+        let revealed =
+            process_password_audit(&mut "alice2024!".as_bytes(), &["alice"], None, true).unwrap();
+        assert_eq!(revealed[0].report.password, "alice2024!");
+        assert!(
+            revealed[0]
+                .report
+                .weaknesses
+                .iter()
+                .any(|w| w.starts_with("context word 'alice'"))
+        );
+    }
+
+    #[test]
+    fn test_audit_masked_output_hides_passwords() {
+        let passwords = ["alice1987qwerty", "zzzzxcvbhunter", "acme!!!!2019"];
+        let policy = PasswordPolicy {
+            forbidden: "!".to_string(),
+            max_repeat: Some(2),
+            banned: vec!["acme".to_string(), "qwer".to_string()],
+            ..Default::default()
+        };
+        let input = passwords.join("\n");
+        let entries =
+            process_password_audit(&mut input.as_bytes(), &["alice"], Some(&policy), false)
+                .unwrap();
+        let output = format!(
+            "{}{}",
+            render_audit_table(&entries),
+            serde_json::to_string(&entries).unwrap()
+        )
+        .to_lowercase();
+        for password in passwords {
+            let chars: Vec<char> = password.chars().collect();
+            for window in chars.windows(4) {
+                let part: String = window.iter().collect();
+                assert!(!output.contains(&part), "{:?} leaked in {}", part, output);
+            }
+        }
+    }
+}
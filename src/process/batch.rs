@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{CsvConvertOptions, cli::OutputFormat, process_csv};
+
+/// Outcome of converting several files in one run
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub succeeded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// Whether `pattern` should be expanded. `[` is also legal in file names, so a path
+/// such as `data[1].csv` that names an existing file is taken literally.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?']) || (pattern.contains('[') && !Path::new(pattern).is_file())
+}
+
+/// Expand a glob pattern into the matching files, sorted by path
+pub fn expand_inputs(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    if !is_glob(pattern) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+    let mut inputs = glob::glob(pattern)
+        .with_context(|| format!("Invalid input pattern: {}", pattern))?
+        .filter_map(|entry| entry.ok().filter(|p| p.is_file()))
+        .collect::<Vec<_>>();
+    inputs.sort();
+    anyhow::ensure!(!inputs.is_empty(), "No input files match {}", pattern);
+    Ok(inputs)
+}
+
+/// Fill `{stem}`, `{name}`, `{dir}` and `{format}` in an output path template
+pub fn render_output(template: &str, input: &Path, format: OutputFormat) -> String {
+    let part = |s: Option<&std::ffi::OsStr>| {
+        s.map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let dir = input
+        .parent()
+        .map(|d| d.to_string_lossy())
+        .unwrap_or_default();
+    template
+        .replace("{stem}", &part(input.file_stem()))
+        .replace("{name}", &part(input.file_name()))
+        .replace("{dir}", if dir.is_empty() { "." } else { &dir })
+        .replace("{format}", format.into())
+}
+
+/// Render the output path of every input up front, failing when two inputs
+/// would write the same file or an output would overwrite one of the inputs
+pub fn plan_outputs(
+    inputs: &[PathBuf],
+    template: &str,
+    format: OutputFormat,
+) -> anyhow::Result<Vec<String>> {
+    let sources: HashMap<PathBuf, &PathBuf> = inputs.iter().map(|i| (path_key(i), i)).collect();
+    let mut targets: HashMap<PathBuf, &PathBuf> = HashMap::new();
+    let mut outputs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let output = render_output(template, input, format);
+        let key = path_key(Path::new(&output));
+        if let Some(source) = sources.get(&key) {
+            anyhow::bail!(
+                "Output {} of {} would overwrite the input {}",
+                output,
+                input.display(),
+                source.display()
+            );
+        }
+        if let Some(other) = targets.insert(key, input) {
+            anyhow::bail!(
+                "{} and {} would both be written to {}, add {{stem}} to the output template",
+                other.display(),
+                input.display(),
+                output
+            );
+        }
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
+
+/// Comparable form of a path: resolved when it exists, otherwise made
+/// absolute so `./a.json` and `a.json` agree
+fn path_key(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Convert every input to the path rendered from `template`, reporting
+/// progress on stderr. Without `keep_going` the run stops at the first
+/// failure. Nothing is converted when the outputs clash, see
+/// [`plan_outputs`].
+pub fn process_csv_batch(
+    inputs: &[PathBuf],
+    template: &str,
+    format: OutputFormat,
+    opts: &CsvConvertOptions,
+    keep_going: bool,
+) -> anyhow::Result<BatchSummary> {
+    let outputs = plan_outputs(inputs, template, format)?;
+    let mut summary = BatchSummary::default();
+    for (i, (input, output)) in inputs.iter().zip(outputs).enumerate() {
+        let ret = create_parent(&output).and_then(|_| {
+            let input = input.to_str().context("Input path is not valid UTF-8")?;
+            process_csv(input, &output, format, opts)
+        });
+        match ret {
+            Ok(stats) => {
+                let sanitized = match opts.sanitize_formulas {
+                    true => format!(" ({} cells sanitized)", stats.sanitized),
+                    false => String::new(),
+                };
+                eprintln!(
+                    "[{}/{}] {} -> {}{}",
+                    i + 1,
+                    inputs.len(),
+                    input.display(),
+                    output,
+                    sanitized
+                );
+                summary.succeeded.push(input.clone());
+            }
+            Err(e) => {
+                eprintln!(
+                    "[{}/{}] {} failed: {:#}",
+                    i + 1,
+                    inputs.len(),
+                    input.display(),
+                    e
+                );
+                summary.failed.push((input.clone(), e));
+                if !keep_going {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn create_parent(output: &str) -> anyhow::Result<()> {
+    match Path::new(output).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            std::fs::create_dir_all(dir).context("Failed to create output directory")
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_output() {
+        let input = Path::new("data/players.csv");
+        assert_eq!(
+            render_output("out/{stem}.{format}", input, OutputFormat::Json),
+            "out/players.json"
+        );
+        assert_eq!(
+            render_output("{dir}/{name}.bak", input, OutputFormat::Csv),
+            "data/players.csv.bak"
+        );
+        assert_eq!(
+            render_output("output.json", input, OutputFormat::Json),
+            "output.json"
+        );
+    }
+
+    #[test]
+    fn test_plan_outputs() {
+        let inputs = vec![
+            PathBuf::from("assets/juventus.csv"),
+            PathBuf::from("data/players.csv"),
+        ];
+        let outputs = plan_outputs(&inputs, "out/{stem}.{format}", OutputFormat::Json).unwrap();
+        assert_eq!(outputs, vec!["out/juventus.json", "out/players.json"]);
+        assert!(plan_outputs(&inputs, "out.json", OutputFormat::Json).is_err());
+        assert!(plan_outputs(&inputs, "./{dir}/{name}", OutputFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_expand_inputs() -> anyhow::Result<()> {
+        assert_eq!(
+            expand_inputs("./assets/*.csv")?,
+            vec![PathBuf::from("assets/juventus.csv")]
+        );
+        assert!(expand_inputs("./assets/*.none").is_err());
+
+        let literal = std::env::temp_dir().join("rcli-batch[1].csv");
+        std::fs::write(&literal, "a\n1\n")?;
+        let literal = literal.to_string_lossy();
+        assert!(!is_glob(&literal));
+        assert_eq!(expand_inputs(&literal)?, vec![PathBuf::from(&*literal)]);
+        assert!(is_glob("./assets/juventus[0-9].csv"));
+        Ok(())
+    }
+}
@@ -1,10 +1,15 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
 use anyhow::Context;
 use convert_case::{Case, Casing};
 use csv::{Reader, StringRecord};
-use erased_serde::Serialize as ErasedSerialize;
 use serde::{Deserialize, Serialize};
 
-use crate::cli::OutputFormat;
+use crate::{ComputedColumn, DateOptions, RowTransform, XmlOptions, XmlWriter, cli::OutputFormat};
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 /// unuse struct Record
@@ -20,71 +25,565 @@ pub struct Record {
     kit: u8,
 }
 
-pub fn process_csv(input: &str, output: &str, format: OutputFormat) -> anyhow::Result<()> {
+/// Csv headers written for [`Record`] rows, its serialized field names
+const RECORD_HEADERS: [&str; 5] = ["Name", "Position", "Dob", "Nationality", "Kit"];
+
+/// Bytes handed to each worker per batch
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+/// Chunks smaller than this are not worth a thread
+const MIN_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Extra settings for csv conversion, the defaults keep a plain conversion
+#[derive(Debug, Clone, Default)]
+pub struct CsvConvertOptions {
+    pub xml: XmlOptions,
+    pub dates: DateOptions,
+    /// columns appended to every row, evaluated in order
+    pub computed: Vec<ComputedColumn>,
+    /// worker threads, 0 uses every available core
+    pub jobs: usize,
+    /// escape cells a spreadsheet would run as a formula, csv output only
+    pub sanitize_formulas: bool,
+}
+
+/// Counts reported after a conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvertStats {
+    pub rows: usize,
+    /// cells changed by formula sanitizing
+    pub sanitized: usize,
+}
+
+impl CsvConvertOptions {
+    fn jobs(&self) -> usize {
+        match self.jobs {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+    }
+}
+
+/// Convert a csv file to `format`.
+///
+/// The input is read in batches split on record boundaries, each chunk is
+/// parsed, transformed and serialized on its own thread and the fragments are
+/// written back in input order. Output goes to a temporary file that replaces
+/// `output` only once the whole input was converted, so converting a file onto
+/// itself is safe.
+pub fn process_csv(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    opts: &CsvConvertOptions,
+) -> anyhow::Result<ConvertStats> {
     let mut reader = Reader::from_path(input).context("Failed to open input file")?;
-    let headers = reader.headers()?.clone();
+    let transform = RowTransform::new(reader.headers()?, opts)?;
+    let data_start = reader.position().byte();
+    let headers = transform.headers();
 
     let record = StringRecord::from(vec!["Name", "Position", "DOB", "Nationality", "Kit Number"]);
-    let match_record = record.eq(&headers);
-    let ret: Box<dyn ErasedSerialize> = match match_record {
-        true => {
-            let mut vec = Vec::with_capacity(128);
-            // deserialize records
-            for result in reader.deserialize() {
-                let record: Record = result.context("Failed to deserialize record")?;
-                // let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-                vec.push(record);
-            }
-            Box::new(vec)
+    let chunk = ChunkConverter {
+        transform: &transform,
+        match_record: record.eq(headers),
+        format,
+        xml: &opts.xml,
+        sanitize: opts.sanitize_formulas && matches!(format, OutputFormat::Csv),
+    };
+
+    let mut file = File::open(input).context("Failed to open input file")?;
+    file.seek(SeekFrom::Start(data_start))?;
+    let tmp = temp_path(output);
+    let ret = File::create(&tmp)
+        .context("Failed to write output file")
+        .and_then(|f| {
+            let mut out = FragmentWriter::new(BufWriter::new(f), format, &opts.xml)?;
+            out.push(chunk.header()?)?;
+            convert_batches(&mut file, out, &chunk, opts.jobs())
+        })
+        .and_then(|stats| {
+            std::fs::rename(&tmp, output).context("Failed to write output file")?;
+            Ok(stats)
+        });
+    if ret.is_err() {
+        // do not leave a partial file behind
+        let _ = std::fs::remove_file(&tmp);
+    }
+    ret
+}
+
+/// Hidden file next to `output`, so the final rename stays on one file system
+fn temp_path(output: &str) -> PathBuf {
+    let path = Path::new(output);
+    let name = path
+        .file_name()
+        .map_or_else(|| "output".into(), |n| n.to_string_lossy());
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Read the input in batches and write the converted chunks in order
+fn convert_batches<W: Write>(
+    file: &mut File,
+    mut out: FragmentWriter<W>,
+    chunk: &ChunkConverter,
+    jobs: usize,
+) -> anyhow::Result<ConvertStats> {
+    let mut buf = Vec::new();
+    loop {
+        let want = (jobs * CHUNK_SIZE) as u64;
+        let eof = file.take(want).read_to_end(&mut buf)? < want as usize;
+        let end = match eof {
+            true => buf.len(),
+            // a single record larger than the batch, keep reading
+            false => match last_record_end(&buf) {
+                Some(end) => end,
+                None => continue,
+            },
+        };
+
+        let chunks = split_records(&buf[..end], jobs);
+        let fragments = match chunks.len() {
+            0 | 1 => chunks
+                .iter()
+                .map(|data| chunk.convert(data))
+                .collect::<Vec<_>>(),
+            _ => std::thread::scope(|s| {
+                let handles: Vec<_> = chunks
+                    .iter()
+                    .map(|data| s.spawn(move || chunk.convert(data)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|h| h.join().expect("csv worker panicked"))
+                    .collect()
+            }),
+        };
+        for fragment in fragments {
+            out.push(fragment?)?;
+        }
+
+        buf.drain(..end);
+        if eof {
+            break;
         }
-        false => {
-            let mut ret = Vec::with_capacity(128);
-            for result in reader.records() {
-                let record = result?;
-                let json_value = headers
+    }
+    out.finish()
+}
+
+/// Serialized rows of one chunk
+struct Fragment {
+    content: Vec<u8>,
+    rows: usize,
+    sanitized: usize,
+}
+
+/// Everything a worker needs to turn raw csv bytes into output
+struct ChunkConverter<'a> {
+    transform: &'a RowTransform<'a>,
+    /// headers match [`Record`], keep its typed output
+    match_record: bool,
+    format: OutputFormat,
+    xml: &'a XmlOptions,
+    /// apply [`sanitize_formula`] to every cell
+    sanitize: bool,
+}
+
+impl ChunkConverter<'_> {
+    /// The csv header row, written once before any chunk so a file without
+    /// records still gets it. Input and `--add-col` names are sanitized like
+    /// any other cell.
+    fn header(&self) -> anyhow::Result<Fragment> {
+        let mut content = vec![];
+        let mut sanitized = 0;
+        if let OutputFormat::Csv = self.format {
+            let mut headers: StringRecord = match self.match_record {
+                true => RECORD_HEADERS.iter().collect(),
+                false => self
+                    .transform
+                    .headers()
                     .iter()
-                    .zip(record.iter())
-                    .collect::<serde_json::Value>();
+                    .map(|h| h.to_case(Case::Pascal))
+                    .collect(),
+            };
+            if self.sanitize {
+                sanitized = sanitize_record(&mut headers);
+            }
+            let mut wtr = csv::Writer::from_writer(vec![]);
+            wtr.write_record(&headers)?;
+            content = wtr.into_inner()?;
+        }
+        Ok(Fragment {
+            content,
+            rows: 0,
+            sanitized,
+        })
+    }
 
-                ret.push(json_value);
+    fn convert(&self, data: &[u8]) -> anyhow::Result<Fragment> {
+        let headers = self.transform.headers();
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(data);
+        let mut records = Vec::new();
+        let mut sanitized = 0;
+        for result in reader.records() {
+            let record = result?;
+            anyhow::ensure!(
+                record.len() == self.transform.input_len(),
+                "Found record with {} fields, but the header has {} fields",
+                record.len(),
+                self.transform.input_len()
+            );
+            let mut record = self.transform.apply(record)?;
+            if self.sanitize {
+                sanitized += sanitize_record(&mut record);
             }
-            Box::new(ret)
+            records.push(record);
         }
-    };
+        let deserialize = || {
+            records
+                .iter()
+                .map(|record| {
+                    record
+                        .deserialize::<Record>(Some(headers))
+                        .context("Failed to deserialize record")
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
 
-    let content: Box<dyn AsRef<[u8]>> = match format {
-        OutputFormat::Json => Box::new(serde_json::to_string_pretty(&ret)?),
-        OutputFormat::Yaml => Box::new(serde_yaml::to_string(&ret)?),
-        OutputFormat::Csv => {
-            let buffer = vec![];
-            let mut wtr = csv::WriterBuilder::new()
-                .has_headers(match_record)
-                .from_writer(buffer);
-            reader = Reader::from_path(input).context("Failed to open input file")?;
-            match match_record {
-                true => {
-                    for result in reader.deserialize() {
-                        let record: Record = result.context("Failed to deserialize record")?;
-                        // let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-                        wtr.serialize(record)?;
+        let content = match (self.format, self.match_record) {
+            (OutputFormat::Json, true) => json_rows(&deserialize()?)?,
+            (OutputFormat::Json, false) => json_rows(&json_values(headers, &records))?,
+            (OutputFormat::Yaml, _) if records.is_empty() => vec![],
+            (OutputFormat::Yaml, true) => serde_yaml::to_string(&deserialize()?)?.into_bytes(),
+            (OutputFormat::Yaml, false) => {
+                serde_yaml::to_string(&json_values(headers, &records))?.into_bytes()
+            }
+            (OutputFormat::Csv, match_record) => {
+                let buffer = vec![];
+                let mut wtr = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(buffer);
+                match match_record {
+                    true => {
+                        for record in deserialize()? {
+                            wtr.serialize(record)?;
+                        }
                     }
-                }
-                false => {
-                    let upper_headers: Vec<String> =
-                        headers.iter().map(|h| h.to_case(Case::Pascal)).collect();
-                    wtr.write_record(&upper_headers)?;
-                    for result in reader.records() {
-                        let record = result?; // StringRecord
-                        wtr.write_record(&record)?;
+                    false => {
+                        for record in &records {
+                            wtr.write_record(record)?;
+                        }
                     }
                 }
+                wtr.flush()?;
+                wtr.into_inner()?
+            }
+            (OutputFormat::Xml, _) => {
+                let mut wtr = XmlWriter::fragment(&headers.iter().collect::<Vec<_>>(), self.xml);
+                for record in &records {
+                    wtr.write_row(record.iter());
+                }
+                wtr.into_fragment()
+            }
+        };
+        Ok(Fragment {
+            content,
+            rows: records.len(),
+            sanitized,
+        })
+    }
+}
+
+fn json_values(headers: &StringRecord, records: &[StringRecord]) -> Vec<serde_json::Value> {
+    records
+        .iter()
+        .map(|record| {
+            headers
+                .iter()
+                .zip(record.iter())
+                .collect::<serde_json::Value>()
+        })
+        .collect()
+}
+
+/// Pretty printed array elements without the surrounding brackets, so chunks
+/// can be joined into one array
+fn json_rows<T: Serialize>(rows: &[T]) -> anyhow::Result<Vec<u8>> {
+    let mut out = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        let pretty = serde_json::to_string_pretty(row)?;
+        for (j, line) in pretty.lines().enumerate() {
+            if j > 0 {
+                out.push('\n');
+            }
+            out.push_str("  ");
+            out.push_str(line);
+        }
+    }
+    Ok(out.into_bytes())
+}
+
+/// Joins chunk fragments into one document of the output format
+struct FragmentWriter<W: Write> {
+    wtr: W,
+    format: OutputFormat,
+    xml: XmlOptions,
+    stats: ConvertStats,
+}
+
+impl<W: Write> FragmentWriter<W> {
+    fn new(mut wtr: W, format: OutputFormat, xml: &XmlOptions) -> anyhow::Result<Self> {
+        if let OutputFormat::Xml = format {
+            wtr.write_all(XmlWriter::prolog(xml).as_bytes())?;
+        }
+        Ok(Self {
+            wtr,
+            format,
+            xml: xml.clone(),
+            stats: ConvertStats::default(),
+        })
+    }
+
+    fn push(&mut self, fragment: Fragment) -> anyhow::Result<()> {
+        if let OutputFormat::Json = self.format
+            && fragment.rows > 0
+        {
+            self.wtr
+                .write_all(if self.stats.rows == 0 { b"[\n" } else { b",\n" })?;
+        }
+        self.wtr.write_all(&fragment.content)?;
+        self.stats.rows += fragment.rows;
+        self.stats.sanitized += fragment.sanitized;
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<ConvertStats> {
+        match (self.format, self.stats.rows) {
+            (OutputFormat::Json, 0) => self.wtr.write_all(b"[]")?,
+            (OutputFormat::Json, _) => self.wtr.write_all(b"\n]")?,
+            (OutputFormat::Yaml, 0) => self.wtr.write_all(b"[]\n")?,
+            (OutputFormat::Xml, _) => self
+                .wtr
+                .write_all(XmlWriter::epilogue(&self.xml).as_bytes())?,
+            _ => {}
+        }
+        self.wtr.flush().context("Failed to write output file")?;
+        Ok(self.stats)
+    }
+}
+
+/// Escape a cell a spreadsheet would evaluate, e.g. `=HYPERLINK(...)`, by
+/// prefixing it with `'`. Plain numbers such as `-5` are left alone.
+pub fn sanitize_formula(cell: &str) -> Option<String> {
+    let risky = cell.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if !risky || cell.trim().parse::<f64>().is_ok() {
+        return None;
+    }
+    Some(format!("'{}", cell))
+}
+
+fn sanitize_record(record: &mut StringRecord) -> usize {
+    let mut changed = 0;
+    let fields: Vec<String> = record
+        .iter()
+        .map(|cell| match sanitize_formula(cell) {
+            Some(safe) => {
+                changed += 1;
+                safe
+            }
+            None => cell.to_string(),
+        })
+        .collect();
+    if changed > 0 {
+        *record = StringRecord::from(fields);
+    }
+    changed
+}
+
+/// Quote aware scan for the end of the last complete record
+fn last_record_end(data: &[u8]) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut end = None;
+    for (i, &b) in data.iter().enumerate() {
+        match b {
+            b'"' => in_quotes = !in_quotes,
+            b'\n' if !in_quotes => end = Some(i + 1),
+            _ => {}
+        }
+    }
+    end
+}
+
+/// Split complete records into at most `parts` chunks of similar size without
+/// cutting a quoted field
+fn split_records(data: &[u8], parts: usize) -> Vec<&[u8]> {
+    let parts = parts.clamp(1, data.len() / MIN_CHUNK_SIZE + 1);
+    let target = data.len() / parts;
+    let mut chunks = Vec::with_capacity(parts);
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, &b) in data.iter().enumerate() {
+        match b {
+            b'"' => in_quotes = !in_quotes,
+            b'\n' if !in_quotes && i + 1 - start >= target && chunks.len() + 1 < parts => {
+                chunks.push(&data[start..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < data.len() {
+        chunks.push(&data[start..]);
+    }
+    chunks
+}
+
+/// Headers and plain string rows of a csv file
+pub type Table = (Vec<String>, Vec<Vec<String>>);
+
+/// Read a csv file into its headers and plain string rows
+pub fn read_rows(input: &str) -> anyhow::Result<Table> {
+    let mut reader = Reader::from_path(input).context("Failed to open input file")?;
+    let headers = reader.headers()?.iter().map(String::from).collect();
+    let rows = reader
+        .records()
+        .map(|record| Ok(record?.iter().map(String::from).collect()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((headers, rows))
+}
+
+/// Serialize plain string rows, used by commands that build their own rows
+/// instead of reading a csv file
+pub fn serialize_rows(
+    headers: &[String],
+    rows: &[Vec<String>],
+    format: OutputFormat,
+    xml: &XmlOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let content = match format {
+        OutputFormat::Json | OutputFormat::Yaml => {
+            let values: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .zip(row.iter())
+                        .map(|(h, v)| (h.clone(), serde_json::Value::from(v.as_str())))
+                        .collect()
+                })
+                .collect();
+            match format {
+                OutputFormat::Json => serde_json::to_string_pretty(&values)?.into_bytes(),
+                _ => serde_yaml::to_string(&values)?.into_bytes(),
+            }
+        }
+        OutputFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(vec![]);
+            wtr.write_record(headers)?;
+            for row in rows {
+                wtr.write_record(row)?;
             }
             wtr.flush()?;
-            Box::new(wtr.into_inner()?)
+            wtr.into_inner()?
+        }
+        OutputFormat::Xml => {
+            let mut wtr = XmlWriter::new(headers, xml);
+            for row in rows {
+                wtr.write_row(row.iter().map(String::as_str));
+            }
+            wtr.into_inner()
         }
     };
 
-    std::fs::write(output, content.as_ref()).context("Failed to write output file")?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_records() {
+        let data = b"1,\"a\nb\"\n2,c\n3,d\n";
+        assert_eq!(last_record_end(b"1,\"a\nb"), None);
+        assert_eq!(last_record_end(data), Some(data.len()));
+        let chunks = split_records(data, 8);
+        assert_eq!(chunks.concat(), data.to_vec());
+        assert!(chunks.iter().all(|c| c.ends_with(b"\n")));
+    }
+
+    #[test]
+    fn test_sanitize_formula() {
+        assert_eq!(sanitize_formula("=1+2").as_deref(), Some("'=1+2"));
+        assert_eq!(sanitize_formula("@SUM(A1)").as_deref(), Some("'@SUM(A1)"));
+        assert_eq!(sanitize_formula("-5"), None);
+        assert_eq!(sanitize_formula("Buffon"), None);
+        let mut record = StringRecord::from(vec!["+cmd|' /C calc'!A0", "ok", "-x"]);
+        assert_eq!(sanitize_record(&mut record), 2);
+        assert_eq!(&record[0], "'+cmd|' /C calc'!A0");
+    }
+
+    #[test]
+    fn test_process_csv_jobs() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let mut outputs = Vec::new();
+        for jobs in [1, 4] {
+            let output = dir.join(format!("rcli-jobs-{}.json", jobs));
+            let opts = CsvConvertOptions {
+                jobs,
+                ..Default::default()
+            };
+            process_csv(
+                "./assets/juventus.csv",
+                output.to_str().unwrap(),
+                OutputFormat::Json,
+                &opts,
+            )?;
+            outputs.push(std::fs::read_to_string(&output)?);
+        }
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&outputs[0])?;
+        assert_eq!(rows.len(), 27);
+        assert_eq!(outputs[0], outputs[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_in_place() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("rcli-in-place.csv");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "name,kit\nBuffon,1\n")?;
+        let stats = process_csv(path, path, OutputFormat::Csv, &Default::default())?;
+        assert_eq!(stats.rows, 1);
+        assert_eq!(std::fs::read_to_string(path)?, "Name,Kit\nBuffon,1\n");
+
+        std::fs::write(path, "name,kit\n")?;
+        process_csv(path, path, OutputFormat::Csv, &Default::default())?;
+        assert_eq!(std::fs::read_to_string(path)?, "Name,Kit\n");
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
 
-    Ok(())
+    #[test]
+    fn test_sanitize_headers() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("rcli-sanitize-headers.csv");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "@cmd,kit\n=1+2,1\n")?;
+        let opts = CsvConvertOptions {
+            sanitize_formulas: true,
+            computed: vec![ComputedColumn {
+                name: "=x".into(),
+                expr: crate::Expr::parse("kit")?,
+            }],
+            ..Default::default()
+        };
+        let stats = process_csv(path, path, OutputFormat::Csv, &opts)?;
+        assert_eq!(stats.sanitized, 3);
+        let output = std::fs::read_to_string(path)?;
+        assert!(output.starts_with("'@cmd,Kit,'=x\n"), "{}", output);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,103 @@
+use std::fmt::Write;
+
+use anyhow::Context;
+use chrono::{
+    NaiveDate,
+    format::{Item, StrftimeItems},
+};
+
+const ISO8601: &str = "%Y-%m-%d";
+
+/// Date columns to normalize during csv conversion
+#[derive(Debug, Clone)]
+pub struct DateOptions {
+    pub columns: Vec<String>,
+    /// chrono format of the input, trailing text after the date is dropped
+    pub input_format: String,
+    /// chrono format of the output, or `iso8601`
+    pub output_format: String,
+    /// derived column holding the current age computed from the first date
+    /// column
+    pub age_column: Option<String>,
+}
+
+impl Default for DateOptions {
+    fn default() -> Self {
+        Self {
+            columns: Vec::new(),
+            input_format: "%b %d, %Y".into(),
+            output_format: "iso8601".into(),
+            age_column: None,
+        }
+    }
+}
+
+impl DateOptions {
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    pub fn output_format(&self) -> &str {
+        match self.output_format.to_lowercase().as_str() {
+            "iso8601" | "iso" => ISO8601,
+            _ => &self.output_format,
+        }
+    }
+}
+
+/// Parse a date with `format`, ignoring annotations after it such as the age in
+/// `Apr 18, 1990 (29)`
+pub fn parse_date(value: &str, format: &str) -> anyhow::Result<NaiveDate> {
+    let (date, _) = NaiveDate::parse_and_remainder(value.trim(), format)
+        .with_context(|| format!("Failed to parse date {:?} with format {:?}", value, format))?;
+    Ok(date)
+}
+
+/// Reject a date output format that chrono cannot render for a plain date, either
+/// because of a bad specifier or because it asks for a time or zone
+pub fn check_date_format(format: &str) -> anyhow::Result<()> {
+    if matches!(format.to_lowercase().as_str(), "iso8601" | "iso") {
+        return Ok(());
+    }
+    anyhow::ensure!(
+        !StrftimeItems::new(format).any(|item| matches!(item, Item::Error)),
+        "Invalid date format {:?}",
+        format
+    );
+    let sample = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+    format_date(sample, format)
+        .map(|_| ())
+        .with_context(|| format!("Date format {:?} needs more than a date", format))
+}
+
+/// `date` written with `format`, an error instead of the panic `to_string` gives
+pub fn format_date(date: NaiveDate, format: &str) -> anyhow::Result<String> {
+    let mut out = String::new();
+    write!(out, "{}", date.format(format))
+        .map_err(|_| anyhow::anyhow!("Failed to format date with {:?}", format))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() -> anyhow::Result<()> {
+        let opts = DateOptions::default();
+        let date = parse_date("Apr 18, 1990 (29)", &opts.input_format)?;
+        assert_eq!(date.format(opts.output_format()).to_string(), "1990-04-18");
+        assert!(parse_date("18/04/1990", &opts.input_format).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_date_format() {
+        assert!(check_date_format("iso8601").is_ok());
+        assert!(check_date_format("%d/%m/%Y").is_ok());
+        assert!(check_date_format("%H:%M").is_err());
+        assert!(check_date_format("%Y-%Q").is_err());
+        let date = NaiveDate::from_ymd_opt(1990, 4, 18).unwrap();
+        assert!(format_date(date, "%H").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::{PassSpec, PasswordPolicy, gen_pass_with};
+
+/// Part of the salt. Bump it together with anything that changes the output, including a
+/// rand upgrade that changes how `gen_pass_with` samples.
+const DERIVE_TAG: &[u8] = b"rcli-genpass-derive-v1";
+// Argon2id, 64 MiB, 3 passes: pinned so a crate upgrade can never change a password
+const ARGON2_MEMORY_KIB: u32 = 64 * 1024;
+const ARGON2_PASSES: u32 = 3;
+const ARGON2_LANES: u32 = 1;
+
+/// Stretch `secret` with Argon2id into the seed of a ChaCha20 rng for one site, login and
+/// counter. The site is trimmed and lower-cased so `Example.com` and `example.com` agree.
+pub fn derive_rng(
+    secret: &[u8],
+    site: &str,
+    login: &str,
+    counter: u32,
+) -> anyhow::Result<ChaCha20Rng> {
+    anyhow::ensure!(!secret.is_empty(), "The master secret is empty");
+    let site = site.trim().to_lowercase();
+    anyhow::ensure!(!site.is_empty(), "A site is required");
+    let mut salt = DERIVE_TAG.to_vec();
+    for field in [site.as_bytes(), login.as_bytes()] {
+        salt.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        salt.extend_from_slice(field);
+    }
+    salt.extend_from_slice(&counter.to_be_bytes());
+    Ok(ChaCha20Rng::from_seed(stretch(secret, &salt)?))
+}
+
+fn stretch(secret: &[u8], salt: &[u8]) -> anyhow::Result<[u8; 32]> {
+    let params = Params::new(ARGON2_MEMORY_KIB, ARGON2_PASSES, ARGON2_LANES, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid argon2 parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// The password for one site, login and counter, built by the same generator and policy
+/// checks as `genpass` but fed from the derived rng.
+pub fn derive_password(
+    secret: &[u8],
+    site: &str,
+    login: &str,
+    counter: u32,
+    spec: &PassSpec,
+    policy: Option<&PasswordPolicy>,
+) -> anyhow::Result<String> {
+    let mut rng = derive_rng(secret, site, login, counter)?;
+    match policy {
+        Some(policy) => policy.generate(|| Ok(gen_pass_with(spec, &mut rng)?)),
+        None => Ok(gen_pass_with(spec, &mut rng)?),
+    }
+}
+
+/// First 4 bytes of an Argon2id hash of the secret under its own salt, shown so a
+/// mistyped master secret is noticed before the password is used. Checking a guess
+/// against it costs the same Argon2id run as deriving a password, but it does confirm a
+/// correct guess, so it is as sensitive as any derived password.
+pub fn secret_fingerprint(secret: &[u8]) -> anyhow::Result<String> {
+    anyhow::ensure!(!secret.is_empty(), "The master secret is empty");
+    let mut salt = DERIVE_TAG.to_vec();
+    salt.extend_from_slice(b"/fingerprint");
+    Ok(hex::encode(&stretch(secret, &salt)?[..4]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_password() -> anyhow::Result<()> {
+        let spec = PassSpec::new(20);
+        let a = derive_password(b"master", "Example.com", "alice", 1, &spec, None)?;
+        assert_eq!(
+            a,
+            derive_password(b"master", " example.com", "alice", 1, &spec, None)?
+        );
+        assert_eq!(a.chars().count(), 20);
+        for other in [
+            derive_password(b"master", "example.com", "alice", 2, &spec, None)?,
+            derive_password(b"master", "example.com", "bob", 1, &spec, None)?,
+            derive_password(b"master", "example.org", "alice", 1, &spec, None)?,
+            derive_password(b"other", "example.com", "alice", 1, &spec, None)?,
+        ] {
+            assert_ne!(a, other);
+        }
+        assert!(derive_password(b"", "example.com", "alice", 1, &spec, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_secret_fingerprint() {
+        let fingerprint = secret_fingerprint(b"master").unwrap();
+        assert_eq!(fingerprint.len(), 8);
+        assert_eq!(fingerprint, secret_fingerprint(b"master").unwrap());
+        assert_ne!(fingerprint, secret_fingerprint(b"mastr").unwrap());
+        assert_ne!(
+            fingerprint,
+            hex::encode(&blake3::hash(b"master").as_bytes()[..4])
+        );
+        assert!(secret_fingerprint(b"").is_err());
+    }
+}
@@ -0,0 +1,453 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, bail};
+
+/// A small expression language over csv columns.
+///
+/// Columns are referenced by their bare header name (spaces allowed, e.g.
+/// `Kit Number`), strings are quoted with `'` or `"`. Supported operators from
+/// lowest to highest precedence: `or`/`||`, `and`/`&&`, `not`/`!`, comparisons
+/// (`== != < <= > >=`), `+ -`, `* /`. `+` concatenates unless both sides are
+/// numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Lit(Value),
+    Column(String),
+    /// column resolved to its index by [`Expr::bind`]
+    Field(usize),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    /// text of a csv field, read as a number only by arithmetic or when
+    /// compared against a number, so `007` stays `007`
+    Field(String),
+    Num(f64),
+    Bool(bool),
+}
+
+/// `NAME=EXPR` as given to `--add-col`
+#[derive(Debug, Clone)]
+pub struct ComputedColumn {
+    pub name: String,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Num(f64),
+    Ident(String),
+    Bool(bool),
+    Op(Op),
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected token {:?} in expression {:?}", token, input);
+        }
+        Ok(expr)
+    }
+
+    /// Resolve column names against `headers` so rows can be evaluated by
+    /// index
+    pub fn bind<S: AsRef<str>>(self, headers: &[S]) -> anyhow::Result<Self> {
+        Ok(match self {
+            Expr::Column(name) => Expr::Field(
+                headers
+                    .iter()
+                    .position(|h| h.as_ref() == name)
+                    .with_context(|| format!("Column not found: {}", name))?,
+            ),
+            Expr::Not(e) => Expr::Not(Box::new(e.bind(headers)?)),
+            Expr::Neg(e) => Expr::Neg(Box::new(e.bind(headers)?)),
+            Expr::Binary(op, l, r) => {
+                Expr::Binary(op, Box::new(l.bind(headers)?), Box::new(r.bind(headers)?))
+            }
+            e => e,
+        })
+    }
+
+    pub fn eval<S: AsRef<str>>(&self, row: &[S]) -> anyhow::Result<Value> {
+        Ok(match self {
+            Expr::Lit(v) => v.clone(),
+            Expr::Column(name) => bail!("Unbound column: {}", name),
+            Expr::Field(idx) => Value::from_field(row.get(*idx).map_or("", |f| f.as_ref())),
+            Expr::Not(e) => Value::Bool(!e.eval(row)?.truthy()),
+            Expr::Neg(e) => Value::Num(-e.eval(row)?.as_num()?),
+            Expr::Binary(op, l, r) => {
+                let l = l.eval(row)?;
+                // short circuit boolean operators
+                match op {
+                    Op::Or if l.truthy() => return Ok(Value::Bool(true)),
+                    Op::And if !l.truthy() => return Ok(Value::Bool(false)),
+                    _ => {}
+                }
+                binary(*op, l, r.eval(row)?)?
+            }
+        })
+    }
+}
+
+fn binary(op: Op, l: Value, r: Value) -> anyhow::Result<Value> {
+    Ok(match op {
+        Op::Or | Op::And => Value::Bool(r.truthy()),
+        Op::Eq => Value::Bool(compare(&l, &r).is_eq()),
+        Op::Ne => Value::Bool(compare(&l, &r).is_ne()),
+        Op::Lt => Value::Bool(compare(&l, &r).is_lt()),
+        Op::Le => Value::Bool(compare(&l, &r).is_le()),
+        Op::Gt => Value::Bool(compare(&l, &r).is_gt()),
+        Op::Ge => Value::Bool(compare(&l, &r).is_ge()),
+        Op::Add => match (l.num(), r.num()) {
+            (Some(a), Some(b)) => Value::Num(a + b),
+            _ => Value::Str(format!("{}{}", l, r)),
+        },
+        Op::Sub => Value::Num(l.as_num()? - r.as_num()?),
+        Op::Mul => Value::Num(l.as_num()? * r.as_num()?),
+        Op::Div => {
+            let divisor = r.as_num()?;
+            anyhow::ensure!(divisor != 0.0, "Division by zero");
+            Value::Num(l.as_num()? / divisor)
+        }
+    })
+}
+
+/// Numeric when both sides read as numbers, like `Add`, otherwise by text
+fn compare(l: &Value, r: &Value) -> std::cmp::Ordering {
+    match (l.num(), r.num()) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        _ => l.to_string().cmp(&r.to_string()),
+    }
+}
+
+impl Value {
+    /// csv fields that look like numbers take part in arithmetic
+    pub fn from_field(field: &str) -> Self {
+        Value::Field(field.to_string())
+    }
+
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Num(n) => *n != 0.0,
+            Value::Field(s) => self.num().map_or(!s.is_empty(), |n| n != 0.0),
+            Value::Str(s) => !s.is_empty(),
+        }
+    }
+
+    /// the numeric reading of a number or of a field that looks like one
+    fn num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Field(s) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+            _ => None,
+        }
+    }
+
+    fn as_num(&self) -> anyhow::Result<f64> {
+        self.num()
+            .with_context(|| format!("Expected a number, got {:?}", self.to_string()))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) | Value::Field(s) => write!(f, "{}", s),
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl FromStr for ComputedColumn {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, expr) = s
+            .split_once('=')
+            .context("Invalid computed column, expected NAME=EXPR")?;
+        let name = name.trim();
+        anyhow::ensure!(!name.is_empty(), "Computed column needs a name");
+        Ok(Self {
+            name: name.to_string(),
+            expr: Expr::parse(expr)?,
+        })
+    }
+}
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    // bare words waiting to be grouped into an identifier, number or keyword
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '\'' | '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => s.extend(chars.next()),
+                        Some(ch) if ch == c => break,
+                        Some(ch) => s.push(ch),
+                        None => bail!("Unterminated string in expression {:?}", input),
+                    }
+                }
+                Token::Str(s)
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '+' => Token::Op(Op::Add),
+            '-' => Token::Op(Op::Sub),
+            '*' => Token::Op(Op::Mul),
+            '/' => Token::Op(Op::Div),
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                let next_eq = chars.next_if_eq(&'=').is_some();
+                match (c, next_eq) {
+                    ('=', true) => Token::Op(Op::Eq),
+                    ('!', true) => Token::Op(Op::Ne),
+                    ('<', true) => Token::Op(Op::Le),
+                    ('>', true) => Token::Op(Op::Ge),
+                    ('<', false) => Token::Op(Op::Lt),
+                    ('>', false) => Token::Op(Op::Gt),
+                    ('!', false) => Token::Not,
+                    ('&', false) if chars.next_if_eq(&'&').is_some() => Token::Op(Op::And),
+                    ('|', false) if chars.next_if_eq(&'|').is_some() => Token::Op(Op::Or),
+                    _ => bail!("Unexpected {:?} in expression {:?}", c, input),
+                }
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some(ch) = chars.next_if(|ch| !is_special(*ch)) {
+                    word.push(ch);
+                }
+                words.push(word);
+                continue;
+            }
+        };
+        flush_words(&mut words, &mut tokens);
+        tokens.push(token);
+    }
+    flush_words(&mut words, &mut tokens);
+    Ok(tokens)
+}
+
+fn is_special(c: char) -> bool {
+    c.is_whitespace() || "'\"()+-*/=!<>&|".contains(c)
+}
+
+/// Join consecutive bare words into one identifier so `Kit Number` needs no
+/// quoting, keywords split the run
+fn flush_words(words: &mut Vec<String>, tokens: &mut Vec<Token>) {
+    let mut ident: Vec<String> = Vec::new();
+    let push_ident = |ident: &mut Vec<String>, tokens: &mut Vec<Token>| {
+        if ident.is_empty() {
+            return;
+        }
+        let joined = ident.join(" ");
+        tokens.push(match joined.parse::<f64>() {
+            Ok(n) if ident.len() == 1 => Token::Num(n),
+            _ => Token::Ident(joined),
+        });
+        ident.clear();
+    };
+    for word in words.drain(..) {
+        let keyword = match word.as_str() {
+            "and" => Token::Op(Op::And),
+            "or" => Token::Op(Op::Or),
+            "not" => Token::Not,
+            "true" => Token::Bool(true),
+            "false" => Token::Bool(false),
+            _ => {
+                ident.push(word);
+                continue;
+            }
+        };
+        push_ident(&mut ident, tokens);
+        tokens.push(keyword);
+    }
+    push_ident(&mut ident, tokens);
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// consume the next token if it is one of `ops`
+    fn op(&mut self, ops: &[Op]) -> Option<Op> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[Op],
+        next: fn(&mut Self) -> anyhow::Result<Expr>,
+    ) -> anyhow::Result<Expr> {
+        let mut expr = next(self)?;
+        while let Some(op) = self.op(ops) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(next(self)?));
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[Op::Or], Self::and)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[Op::And], Self::not)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Expr> {
+        let ops = [Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge];
+        self.binary(&ops, Self::additive)
+    }
+
+    fn additive(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[Op::Add, Op::Sub], Self::multiplicative)
+    }
+
+    fn multiplicative(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[Op::Mul, Op::Div], Self::unary)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.op(&[Op::Sub]).is_some() {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> anyhow::Result<Expr> {
+        Ok(match self.next() {
+            Some(Token::Str(s)) => Expr::Lit(Value::Str(s)),
+            Some(Token::Num(n)) => Expr::Lit(Value::Num(n)),
+            Some(Token::Bool(b)) => Expr::Lit(Value::Bool(b)),
+            Some(Token::Ident(name)) => Expr::Column(name),
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                anyhow::ensure!(self.next() == Some(Token::RParen), "Missing )");
+                expr
+            }
+            Some(token) => bail!("Unexpected token {:?}", token),
+            None => bail!("Unexpected end of expression"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADERS: [&str; 3] = ["Name", "Position", "Kit Number"];
+
+    fn eval(expr: &str, row: &[&str]) -> anyhow::Result<String> {
+        let expr = Expr::parse(expr)?.bind(&HEADERS)?;
+        Ok(expr.eval(row)?.to_string())
+    }
+
+    #[test]
+    fn test_expr_eval() -> anyhow::Result<()> {
+        let row = ["Gianluigi Buffon", "Goalkeeper", "77"];
+        assert_eq!(
+            eval("Name + ' #' + Kit Number", &row)?,
+            "Gianluigi Buffon #77"
+        );
+        assert_eq!(eval("Position == 'Goalkeeper'", &row)?, "true");
+        assert_eq!(eval("Kit Number * 2 + 1", &row)?, "155");
+        assert_eq!(eval("(Kit Number - 7) / 10", &row)?, "7");
+        assert_eq!(
+            eval("Kit Number > 10 and not Position != \"Goalkeeper\"", &row)?,
+            "true"
+        );
+        assert_eq!(eval("Kit Number < 10 || false", &row)?, "false");
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_keeps_field_text() -> anyhow::Result<()> {
+        let row = ["Moise Kean", "Forward", "007"];
+        assert_eq!(eval("Name + ' #' + Kit Number", &row)?, "Moise Kean #007");
+        assert_eq!(eval("Kit Number == '7'", &row)?, "false");
+        assert_eq!(eval("Kit Number == '007'", &row)?, "true");
+        assert_eq!(eval("Kit Number == 7", &row)?, "true");
+        assert_eq!(eval("Kit Number + 1", &row)?, "8");
+
+        let row = ["Moise Kean", "Forward", "1e3"];
+        assert_eq!(eval("'#' + Kit Number", &row)?, "#1e3");
+        assert_eq!(eval("Kit Number == '1000'", &row)?, "false");
+        assert_eq!(eval("Kit Number == 1000", &row)?, "true");
+        assert_eq!(eval("Kit Number / 10", &row)?, "100");
+
+        // two numeric fields compare as numbers, not as "10" < "9"
+        let row = ["Moise Kean", "9", "10"];
+        assert_eq!(eval("Kit Number > Position", &row)?, "true");
+        assert_eq!(eval("Kit Number + Position", &row)?, "19");
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_errors() {
+        assert!(Expr::parse("Name +").is_err());
+        assert!(Expr::parse("'open").is_err());
+        assert!(Expr::parse("Missing == 1").unwrap().bind(&HEADERS).is_err());
+        assert!(eval("Name - 1", &["a", "b", "1"]).is_err());
+        assert!("label".parse::<ComputedColumn>().is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use serde::Deserialize;
+
+use crate::{XmlOptions, check_date_format, cli::OutputFormat, format_date, serialize_rows};
+
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Andrea", "Carlo", "Daniele", "Federico", "Giorgio", "Juan", "Leonardo", "Luca",
+    "Marco", "Mattia", "Paulo", "Rodrigo", "Sami", "Wojciech", "Blaise", "Emre", "Moise",
+];
+const LAST_NAMES: &[&str] = &[
+    "Bernardeschi",
+    "Bonucci",
+    "Chiellini",
+    "Costa",
+    "Cuadrado",
+    "De Sciglio",
+    "Dybala",
+    "Higuain",
+    "Khedira",
+    "Matuidi",
+    "Pjanic",
+    "Perin",
+    "Rugani",
+    "Sandro",
+    "Szczesny",
+    "Kean",
+    "Pinsoglio",
+    "Ramsey",
+];
+
+/// Column list of a fake data schema, read from YAML or JSON
+#[derive(Debug, Deserialize)]
+pub struct FakeSchema {
+    pub columns: Vec<FakeColumn>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FakeColumn {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: FakeKind,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FakeKind {
+    /// `First Last` drawn from a built in list
+    Name,
+    /// one of `values`
+    Enum { values: Vec<String> },
+    /// integer in `min..=max`
+    Int { min: i64, max: i64 },
+    /// float in `min..max` rounded to `precision` decimals
+    Float {
+        min: f64,
+        max: f64,
+        #[serde(default = "default_precision")]
+        precision: usize,
+    },
+    /// date in `min..=max` written with a chrono `format`, `age` appends the
+    /// age on the reference date like `Apr 18, 1990 (29)`
+    Date {
+        min: NaiveDate,
+        max: NaiveDate,
+        #[serde(default = "default_date_format")]
+        format: String,
+        #[serde(default)]
+        age: bool,
+    },
+    /// sequential unique id with an optional prefix
+    Id {
+        #[serde(default)]
+        prefix: String,
+    },
+    /// random unique integer in `min..=max`
+    Unique { min: i64, max: i64 },
+}
+
+fn default_precision() -> usize {
+    2
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".into()
+}
+
+/// Generate `rows` fake rows from a YAML/JSON `schema` and write them in
+/// `format`. The same `seed` and `today` always produce the same rows.
+pub fn process_csv_fake(
+    schema: &str,
+    rows: usize,
+    seed: Option<u64>,
+    today: Option<NaiveDate>,
+    output: &str,
+    format: OutputFormat,
+    xml: &XmlOptions,
+) -> anyhow::Result<()> {
+    let schema: FakeSchema = serde_yaml::from_str(schema).context("Failed to parse schema")?;
+    let (headers, rows) = gen_fake_rows(&schema, rows, seed, today)?;
+    let content = serialize_rows(&headers, &rows, format, xml)?;
+    std::fs::write(output, content).context("Failed to write output file")?;
+    Ok(())
+}
+
+/// Ages are computed on `today`, which a seeded run must be given when the schema
+/// has ages, otherwise the rows would change from day to day
+pub fn gen_fake_rows(
+    schema: &FakeSchema,
+    rows: usize,
+    seed: Option<u64>,
+    today: Option<NaiveDate>,
+) -> anyhow::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let has_age = schema
+        .columns
+        .iter()
+        .any(|c| matches!(c.kind, FakeKind::Date { age: true, .. }));
+    anyhow::ensure!(
+        seed.is_none() || today.is_some() || !has_age,
+        "A seeded schema with ages needs a reference date (--today) to be reproducible"
+    );
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let today = today.unwrap_or_else(|| Local::now().date_naive());
+    let width = rows.to_string().len();
+
+    let mut generators = schema
+        .columns
+        .iter()
+        .map(|col| FakeGenerator::new(&col.kind, rows).with_context(|| col.name.clone()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut ret = Vec::with_capacity(rows);
+    for idx in 0..rows {
+        let row = generators
+            .iter_mut()
+            .map(|g| g.next(&mut rng, idx, width, today))
+            .collect::<anyhow::Result<_>>()?;
+        ret.push(row);
+    }
+    let headers = schema.columns.iter().map(|c| c.name.clone()).collect();
+    Ok((headers, ret))
+}
+
+struct FakeGenerator<'a> {
+    kind: &'a FakeKind,
+    /// values already handed out by [`FakeKind::Unique`]
+    seen: HashSet<i64>,
+}
+
+impl<'a> FakeGenerator<'a> {
+    fn new(kind: &'a FakeKind, rows: usize) -> anyhow::Result<Self> {
+        if let FakeKind::Date { format, .. } = kind {
+            check_date_format(format)?;
+        }
+        match kind {
+            FakeKind::Enum { values } if values.is_empty() => {
+                anyhow::bail!("enum needs at least one value")
+            }
+            FakeKind::Int { min, max } if min > max => anyhow::bail!("min is larger than max"),
+            FakeKind::Float { min, max, .. } if !(max - min).is_finite() => {
+                anyhow::bail!("min and max must be finite and their span representable")
+            }
+            FakeKind::Float { min, max, .. } if min >= max => {
+                anyhow::bail!("min must be smaller than max")
+            }
+            FakeKind::Date { min, max, .. } if min > max => {
+                anyhow::bail!("min is later than max")
+            }
+            // i128 so that spans such as i64::MIN..=i64::MAX cannot overflow
+            FakeKind::Unique { min, max }
+                if min > max || (*max as i128 - *min as i128 + 1) < rows as i128 =>
+            {
+                anyhow::bail!("range too small for {} unique values", rows)
+            }
+            _ => Ok(Self {
+                kind,
+                seen: HashSet::new(),
+            }),
+        }
+    }
+
+    fn next(
+        &mut self,
+        rng: &mut StdRng,
+        idx: usize,
+        width: usize,
+        today: NaiveDate,
+    ) -> anyhow::Result<String> {
+        Ok(match self.kind {
+            FakeKind::Name => format!(
+                "{} {}",
+                FIRST_NAMES.choose(rng).unwrap_or(&""),
+                LAST_NAMES.choose(rng).unwrap_or(&"")
+            ),
+            FakeKind::Enum { values } => values.choose(rng).cloned().unwrap_or_default(),
+            FakeKind::Int { min, max } => rng.gen_range(*min..=*max).to_string(),
+            FakeKind::Float {
+                min,
+                max,
+                precision,
+            } => format!("{:.*}", precision, rng.gen_range(*min..*max)),
+            FakeKind::Date {
+                min,
+                max,
+                format,
+                age,
+            } => {
+                let days = (*max - *min).num_days();
+                let date = *min + Duration::days(rng.gen_range(0..=days));
+                let mut value = format_date(date, format)?;
+                if *age {
+                    value.push_str(&format!(" ({})", age_at(date, today)));
+                }
+                value
+            }
+            FakeKind::Id { prefix } => format!("{}{:0width$}", prefix, idx + 1, width = width),
+            FakeKind::Unique { min, max } => loop {
+                let value = rng.gen_range(*min..=*max);
+                if self.seen.insert(value) {
+                    break value.to_string();
+                }
+            },
+        })
+    }
+}
+
+/// whole years between `date` and `today`
+pub fn age_at(date: NaiveDate, today: NaiveDate) -> i32 {
+    let mut age = today.year() - date.year();
+    if (today.month(), today.day()) < (date.month(), date.day()) {
+        age -= 1;
+    }
+    age
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+columns:
+  - name: Id
+    type: id
+    prefix: P
+  - name: Name
+    type: name
+  - name: Position
+    type: enum
+    values: [Goalkeeper, Defender]
+  - name: DOB
+    type: date
+    min: 1978-01-01
+    max: 2001-12-31
+    format: "%b %d, %Y"
+    age: true
+  - name: Kit Number
+    type: unique
+    min: 1
+    max: 99
+"#;
+
+    #[test]
+    fn test_gen_fake_rows() -> anyhow::Result<()> {
+        let schema: FakeSchema = serde_yaml::from_str(SCHEMA)?;
+        let today = NaiveDate::from_ymd_opt(2019, 4, 17);
+        let (headers, rows) = gen_fake_rows(&schema, 50, Some(42), today)?;
+        assert_eq!(headers.len(), 5);
+        assert_eq!(rows.len(), 50);
+        assert_eq!(rows[0][0], "P01");
+        let kits: HashSet<_> = rows.iter().map(|r| r[4].clone()).collect();
+        assert_eq!(kits.len(), 50);
+
+        let (_, again) = gen_fake_rows(&schema, 50, Some(42), today)?;
+        assert_eq!(rows, again);
+        assert!(gen_fake_rows(&schema, 50, Some(42), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fake_generator_bounds() {
+        let wide = FakeKind::Unique {
+            min: i64::MIN,
+            max: i64::MAX,
+        };
+        assert!(FakeGenerator::new(&wide, 10).is_ok());
+        let narrow = FakeKind::Unique { min: 1, max: 5 };
+        assert!(FakeGenerator::new(&narrow, 6).is_err());
+        for (min, max) in [(f64::NAN, 1.0), (0.0, f64::INFINITY), (-f64::MAX, f64::MAX)] {
+            let kind = FakeKind::Float {
+                min,
+                max,
+                precision: 2,
+            };
+            assert!(FakeGenerator::new(&kind, 1).is_err(), "{}..{}", min, max);
+        }
+        let date = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let time = FakeKind::Date {
+            min: date,
+            max: date,
+            format: "%H:%M".into(),
+            age: false,
+        };
+        assert!(FakeGenerator::new(&time, 1).is_err());
+    }
+
+    #[test]
+    fn test_age_at() {
+        let today = NaiveDate::from_ymd_opt(2019, 4, 17).unwrap();
+        let dob = NaiveDate::from_ymd_opt(1990, 4, 18).unwrap();
+        assert_eq!(age_at(dob, today), 28);
+    }
+}
@@ -1,39 +1,682 @@
-use rand::seq::SliceRandom;
-const UPPER_CASE: &[u8] = b"ABCDEFGHIJKLMNPQRSTUVWXYZ";
-const LOWER_CASE: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
-const NUMBER: &[u8] = b"123456789";
-const SYMBOL: &[u8] = b"!@#$%^&*_";
-pub fn gen_pass(
-    length: u8,
-    upper_case: bool,
-    lower_case: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut pass = Vec::new();
-    let mut chars = Vec::new();
-    if upper_case {
-        chars.extend_from_slice(UPPER_CASE);
-        pass.push(*UPPER_CASE.choose(&mut rng).unwrap());
-    }
-    if lower_case {
-        chars.extend_from_slice(LOWER_CASE);
-        pass.push(*LOWER_CASE.choose(&mut rng).unwrap());
-    }
-    if number {
-        chars.extend_from_slice(NUMBER);
-        pass.push(*NUMBER.choose(&mut rng).unwrap());
-    }
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        pass.push(*SYMBOL.choose(&mut rng).unwrap());
-    }
-    for _ in 0..(length - pass.len() as u8) {
-        pass.push(*chars.choose(&mut rng).unwrap());
-    }
-    pass.shuffle(&mut rng);
-    let pass = String::from_utf8(pass).expect("Failed to convert Vec<u8> to String");
-
-    Ok(pass)
+use std::{collections::HashSet, fmt};
+
+#[cfg(test)]
+use rand::SeedableRng;
+use rand::{CryptoRng, RngCore, rngs::OsRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zxcvbn::{Match, matching::patterns::MatchPattern, time_estimates::CrackTimeSeconds};
+
+const UPPER_CASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER_CASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const NUMBER: &str = "0123456789";
+const SYMBOL: &str = "!@#$%^&*_";
+/// Longest password `gen_pass` will produce.
+pub const MAX_LENGTH: u16 = 4096;
+/// Characters that are easy to misread, left out by `--exclude-ambiguous`.
+pub const AMBIGUOUS: &str = "O0Il1|";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GenPassError {
+    #[error("Password length {0} is out of range, expected 1 to {MAX_LENGTH}")]
+    Length(u16),
+    #[error("At least one character class must be enabled")]
+    NoClasses,
+    #[error("Invalid character class '{0}', expected one of u, l, n, s")]
+    InvalidClass(char),
+    #[error("No {class} characters are left after exclusions, but {min} required")]
+    ClassExhausted { class: &'static str, min: usize },
+    #[error("The character pool is empty after exclusions")]
+    EmptyPool,
+    #[error(
+        "Password length {length} is shorter than the {required} characters required by the class minimums"
+    )]
+    TooShort { length: u16, required: usize },
+    #[error(
+        "Password length {length} leaves no room for a syllable after {digits} digits and {symbols} symbols"
+    )]
+    NoSyllables {
+        length: u16,
+        digits: usize,
+        symbols: usize,
+    },
+    #[error("Only {syllables} syllables to capitalize, {upper} requested")]
+    TooManyCapitals { syllables: usize, upper: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Upper,
+    Lower,
+    Number,
+    Symbol,
+    /// A user supplied alphabet that replaces the built-in classes.
+    Custom,
+}
+
+impl CharClass {
+    pub const ALL: [CharClass; 4] = [
+        CharClass::Upper,
+        CharClass::Lower,
+        CharClass::Number,
+        CharClass::Symbol,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CharClass::Upper => "upper case",
+            CharClass::Lower => "lower case",
+            CharClass::Number => "digit",
+            CharClass::Symbol => "symbol",
+            CharClass::Custom => "custom charset",
+        }
+    }
+
+    /// Whether `c` belongs to this class; anything printable that is not a letter or digit
+    /// counts as a symbol.
+    pub fn matches(self, c: char) -> bool {
+        match self {
+            CharClass::Upper => c.is_uppercase(),
+            CharClass::Lower => c.is_lowercase(),
+            CharClass::Number => c.is_ascii_digit(),
+            CharClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace() && !c.is_control(),
+            CharClass::Custom => false,
+        }
+    }
+
+    pub fn default_chars(self) -> &'static str {
+        match self {
+            CharClass::Upper => UPPER_CASE,
+            CharClass::Lower => LOWER_CASE,
+            CharClass::Number => NUMBER,
+            CharClass::Symbol => SYMBOL,
+            CharClass::Custom => "",
+        }
+    }
+
+    /// Parse a class spec such as `ulns`, one letter per class.
+    pub fn parse_set(spec: &str) -> Result<Vec<CharClass>, GenPassError> {
+        let mut classes = Vec::new();
+        for c in spec.chars() {
+            let class = match c {
+                'u' => CharClass::Upper,
+                'l' => CharClass::Lower,
+                'n' | 'd' => CharClass::Number,
+                's' => CharClass::Symbol,
+                _ => return Err(GenPassError::InvalidClass(c)),
+            };
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        if classes.is_empty() {
+            return Err(GenPassError::NoClasses);
+        }
+        Ok(classes)
+    }
+}
+
+/// An enabled character class, its alphabet and how many of its characters the password must
+/// contain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassRule {
+    pub class: CharClass,
+    pub chars: Vec<char>,
+    pub min: usize,
+}
+
+impl ClassRule {
+    pub fn new(class: CharClass, min: usize) -> Self {
+        Self::with_chars(class, class.default_chars(), min)
+    }
+
+    pub fn with_chars(class: CharClass, chars: &str, min: usize) -> Self {
+        let mut set = Vec::new();
+        for c in chars.chars() {
+            if !set.contains(&c) {
+                set.push(c);
+            }
+        }
+        Self {
+            class,
+            chars: set,
+            min,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassSpec {
+    pub length: u16,
+    pub classes: Vec<ClassRule>,
+}
+
+impl Default for PassSpec {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl PassSpec {
+    /// All classes enabled, at least one character of each.
+    pub fn new(length: u16) -> Self {
+        Self {
+            length,
+            classes: CharClass::ALL
+                .iter()
+                .map(|&class| ClassRule::new(class, 1))
+                .collect(),
+        }
+    }
+
+    /// Remove every character in `chars` from all class alphabets.
+    pub fn exclude(&mut self, chars: &str) {
+        for rule in &mut self.classes {
+            rule.chars.retain(|c| !chars.contains(*c));
+        }
+    }
+
+    /// The distinct characters any position of the password may take.
+    pub fn pool(&self) -> Vec<char> {
+        let mut pool = Vec::new();
+        for c in self.classes.iter().flat_map(|r| &r.chars) {
+            if !pool.contains(c) {
+                pool.push(*c);
+            }
+        }
+        pool
+    }
+
+    /// Bits of entropy of a password drawn uniformly from the pool. Class minimums make
+    /// the real figure marginally lower.
+    pub fn entropy_bits(&self) -> f64 {
+        self.length as f64 * (self.pool().len() as f64).log2()
+    }
+
+    fn validate(&self) -> Result<(), GenPassError> {
+        if !(1..=MAX_LENGTH).contains(&self.length) {
+            return Err(GenPassError::Length(self.length));
+        }
+        if self.classes.is_empty() {
+            return Err(GenPassError::NoClasses);
+        }
+        if let Some(rule) = self
+            .classes
+            .iter()
+            .find(|r| r.min > 0 && r.chars.is_empty())
+        {
+            return Err(GenPassError::ClassExhausted {
+                class: rule.class.name(),
+                min: rule.min,
+            });
+        }
+        if self.pool().is_empty() {
+            return Err(GenPassError::EmptyPool);
+        }
+        let required: usize = self.classes.iter().map(|r| r.min).sum();
+        if required > self.length as usize {
+            return Err(GenPassError::TooShort {
+                length: self.length,
+                required,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Generate a password for `spec` from the operating system's CSPRNG.
+pub fn gen_pass(spec: &PassSpec) -> Result<String, GenPassError> {
+    gen_pass_with(spec, &mut OsRng)
+}
+
+/// Reproducible passwords for tests; deliberately not available in release builds.
+#[cfg(test)]
+pub fn gen_pass_seeded(spec: &PassSpec, seed: u64) -> Result<String, GenPassError> {
+    gen_pass_with(spec, &mut rand::rngs::StdRng::seed_from_u64(seed))
+}
+
+/// Generate a password for `spec` drawing from `rng`; a seeded rng gives a reproducible
+/// password, which is what `genpass derive` relies on.
+pub fn gen_pass_with<R: RngCore + CryptoRng + ?Sized>(
+    spec: &PassSpec,
+    rng: &mut R,
+) -> Result<String, GenPassError> {
+    spec.validate()?;
+    let mut pass = Vec::with_capacity(spec.length as usize);
+    for rule in &spec.classes {
+        for _ in 0..rule.min {
+            pass.push(*rule.chars.choose(rng).ok_or(GenPassError::EmptyPool)?);
+        }
+    }
+    let pool = spec.pool();
+    while pass.len() < spec.length as usize {
+        pass.push(*pool.choose(rng).ok_or(GenPassError::EmptyPool)?);
+    }
+    pass.shuffle(rng);
+
+    Ok(pass.into_iter().collect())
+}
+
+/// Give up after this many candidates that miss the target.
+pub(crate) const MAX_ATTEMPTS: usize = 1000;
+/// log10 of the guesses zxcvbn requires for each score.
+const SCORE_GUESSES_LOG10: [f64; 5] = [0.0, 3.0, 6.0, 8.0, 10.0];
+
+/// Minimum strength a generated password must reach.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StrengthTarget {
+    pub min_score: Option<u8>,
+    pub min_entropy: Option<f64>,
+}
+
+impl StrengthTarget {
+    /// Fail when a generator with `entropy_bits` of entropy can never meet the target:
+    /// the entropy is fixed by the settings, and a search space smaller than a score's
+    /// guess threshold cannot honestly earn that score.
+    pub fn check_reachable(&self, entropy_bits: f64) -> anyhow::Result<()> {
+        if let Some(min) = self.min_entropy {
+            anyhow::ensure!(
+                entropy_bits >= min,
+                "These settings give {:.1} bits of entropy, below the required {:.1}; use a longer password or passphrase",
+                entropy_bits,
+                min
+            );
+        }
+        if let Some(score) = self.min_score {
+            let needed = SCORE_GUESSES_LOG10[score.min(4) as usize];
+            anyhow::ensure!(
+                entropy_bits * 2f64.log10() >= needed,
+                "These settings allow at most 10^{:.1} passwords, too few for score {} (10^{} guesses); use a longer password or passphrase",
+                entropy_bits * 2f64.log10(),
+                score,
+                needed
+            );
+        }
+        Ok(())
+    }
+
+    /// Call `generate` until a candidate meets the target, returning it with its report.
+    pub fn generate(
+        &self,
+        entropy_bits: f64,
+        mut generate: impl FnMut() -> anyhow::Result<String>,
+    ) -> anyhow::Result<(String, StrengthReport)> {
+        self.check_reachable(entropy_bits)?;
+        let min_score = self.min_score.unwrap_or(0);
+        for _ in 0..MAX_ATTEMPTS {
+            let password = generate()?;
+            let report = StrengthReport::new(&password, &[]).with_entropy(entropy_bits);
+            if report.score >= min_score {
+                return Ok((password, report));
+            }
+        }
+        anyhow::bail!(
+            "No password reached score {} in {} attempts; use a longer password or passphrase",
+            min_score,
+            MAX_ATTEMPTS
+        )
+    }
+}
+
+/// Call `generate` `count` times. With `unique` set, duplicates are drawn again; the run
+/// fails up front when `entropy_bits` cannot yield that many distinct passwords, and
+/// after too many duplicates in a row.
+pub fn gen_pass_batch<T>(
+    count: usize,
+    unique: bool,
+    entropy_bits: f64,
+    mut generate: impl FnMut() -> anyhow::Result<(String, T)>,
+) -> anyhow::Result<Vec<(String, T)>> {
+    if unique {
+        anyhow::ensure!(
+            (count as f64).log2() <= entropy_bits,
+            "These settings cannot produce {} unique passwords",
+            count
+        );
+    }
+    let mut seen = HashSet::new();
+    let mut out = Vec::with_capacity(count);
+    let mut duplicates = 0;
+    while out.len() < count {
+        let (password, extra) = generate()?;
+        if unique && !seen.insert(password.clone()) {
+            duplicates += 1;
+            anyhow::ensure!(
+                duplicates < MAX_ATTEMPTS,
+                "Only {} unique passwords were generated before repeats took over",
+                out.len()
+            );
+            continue;
+        }
+        duplicates = 0;
+        out.push((password, extra));
+    }
+    Ok(out)
+}
+
+/// zxcvbn's analysis of a password, flattened into something serializable.
+#[derive(Debug, Serialize)]
+pub struct StrengthReport {
+    pub password: String,
+    pub score: u8,
+    pub guesses: u64,
+    pub guesses_log10: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entropy_bits: Option<f64>,
+    pub crack_times: Vec<CrackTime>,
+    /// Guessable parts zxcvbn found, such as dictionary words or keyboard walks.
+    pub weaknesses: Vec<String>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CrackTime {
+    pub scenario: &'static str,
+    pub seconds: f64,
+    pub display: String,
+}
+
+impl StrengthReport {
+    pub fn new(password: &str, user_inputs: &[&str]) -> Self {
+        Self::analyze(password, user_inputs, true)
+    }
+
+    /// A report safe to show others: the password is masked and weaknesses name only
+    /// the kind and length of each guessable part, never its text.
+    pub fn masked(password: &str, user_inputs: &[&str]) -> Self {
+        let mut report = Self::analyze(password, user_inputs, false);
+        report.password = crate::mask_password(password);
+        report
+    }
+
+    fn analyze(password: &str, user_inputs: &[&str], reveal: bool) -> Self {
+        let entropy = zxcvbn::zxcvbn(password, user_inputs);
+        let times = entropy.crack_times();
+        let crack_times = [
+            (
+                "online_throttling_100_per_hour",
+                times.online_throttling_100_per_hour(),
+            ),
+            (
+                "online_no_throttling_10_per_second",
+                times.online_no_throttling_10_per_second(),
+            ),
+            (
+                "offline_slow_hashing_1e4_per_second",
+                times.offline_slow_hashing_1e4_per_second(),
+            ),
+            (
+                "offline_fast_hashing_1e10_per_second",
+                times.offline_fast_hashing_1e10_per_second(),
+            ),
+        ]
+        .into_iter()
+        .map(|(scenario, t)| CrackTime {
+            scenario,
+            seconds: match t {
+                CrackTimeSeconds::Integer(s) => s as f64,
+                CrackTimeSeconds::Float(s) => s,
+            },
+            display: t.to_string(),
+        })
+        .collect();
+        let feedback = entropy.feedback();
+        Self {
+            password: password.to_string(),
+            score: entropy.score().into(),
+            guesses: entropy.guesses(),
+            guesses_log10: entropy.guesses_log10(),
+            entropy_bits: None,
+            crack_times,
+            weaknesses: entropy
+                .sequence()
+                .iter()
+                .filter_map(|m| describe_match(m, user_inputs, reveal))
+                .collect(),
+            warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+            suggestions: feedback
+                .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Attach the generator's own entropy estimate, which zxcvbn cannot know.
+    pub fn with_entropy(mut self, bits: f64) -> Self {
+        self.entropy_bits = Some(bits);
+        self
+    }
+}
+
+impl fmt::Display for StrengthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Score: {}/4", self.score)?;
+        writeln!(
+            f,
+            "Guesses: {} (10^{:.2})",
+            self.guesses, self.guesses_log10
+        )?;
+        if let Some(bits) = self.entropy_bits {
+            writeln!(f, "Entropy: {:.1} bits", bits)?;
+        }
+        writeln!(f, "Crack times:")?;
+        for t in &self.crack_times {
+            writeln!(f, "  {:<38} {}", t.scenario, t.display)?;
+        }
+        for w in &self.weaknesses {
+            writeln!(f, "Weakness: {}", w)?;
+        }
+        if let Some(warning) = &self.warning {
+            writeln!(f, "Warning: {}", warning)?;
+        }
+        for s in &self.suggestions {
+            writeln!(f, "Suggestion: {}", s)?;
+        }
+        Ok(())
+    }
+}
+
+fn describe_match(m: &Match, user_inputs: &[&str], reveal: bool) -> Option<String> {
+    let kind = match &m.pattern {
+        MatchPattern::Dictionary(d) => {
+            let kind = if user_inputs
+                .iter()
+                .any(|w| w.eq_ignore_ascii_case(&d.matched_word))
+            {
+                "context word"
+            } else if d.reversed {
+                "reversed dictionary word"
+            } else {
+                "dictionary word"
+            };
+            let l33t = if d.l33t {
+                " with l33t substitutions"
+            } else {
+                ""
+            };
+            return Some(format!("{} {}{}", kind, show_token(m, reveal), l33t));
+        }
+        MatchPattern::Spatial(_) => "keyboard pattern",
+        MatchPattern::Repeat(_) => "repeated characters",
+        MatchPattern::Sequence(_) => "sequence",
+        MatchPattern::Regex(_) => "predictable pattern",
+        MatchPattern::Date(_) => "date",
+        MatchPattern::BruteForce => return None,
+    };
+    Some(format!("{} {}", kind, show_token(m, reveal)))
+}
+
+fn show_token(m: &Match, reveal: bool) -> String {
+    match reveal {
+        true => format!("'{}'", m.token),
+        false => format!("({} characters)", m.token.chars().count()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strength_report() {
+        let report = StrengthReport::new("password123", &[]);
+        assert_eq!(report.score, 0);
+        assert_eq!(report.crack_times.len(), 4);
+        assert!(!report.suggestions.is_empty() || report.warning.is_some());
+        let strong = StrengthReport::new(&gen_pass(&PassSpec::new(24)).unwrap(), &[]);
+        assert_eq!(strong.score, 4);
+    }
+
+    #[test]
+    fn test_class_minimums() {
+        let spec = PassSpec {
+            length: 8,
+            classes: vec![
+                ClassRule::new(CharClass::Lower, 0),
+                ClassRule::new(CharClass::Number, 5),
+            ],
+        };
+        for _ in 0..50 {
+            let pass = gen_pass(&spec).unwrap();
+            assert_eq!(pass.len(), 8);
+            assert!(pass.bytes().filter(u8::is_ascii_digit).count() >= 5);
+            assert!(
+                pass.bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_specs() {
+        assert_eq!(
+            gen_pass(&PassSpec::new(3)),
+            Err(GenPassError::TooShort {
+                length: 3,
+                required: 4
+            })
+        );
+        assert_eq!(gen_pass(&PassSpec::new(0)), Err(GenPassError::Length(0)));
+        assert_eq!(
+            gen_pass(&PassSpec::new(MAX_LENGTH + 1)),
+            Err(GenPassError::Length(MAX_LENGTH + 1))
+        );
+        let empty = PassSpec {
+            length: 8,
+            classes: vec![],
+        };
+        assert_eq!(gen_pass(&empty), Err(GenPassError::NoClasses));
+        assert_eq!(
+            CharClass::parse_set("lnl").unwrap(),
+            vec![CharClass::Lower, CharClass::Number]
+        );
+        assert_eq!(
+            CharClass::parse_set("x"),
+            Err(GenPassError::InvalidClass('x'))
+        );
+    }
+
+    #[test]
+    fn test_gen_pass_long_and_seeded() {
+        let pass = gen_pass(&PassSpec::new(MAX_LENGTH)).unwrap();
+        assert_eq!(pass.len(), MAX_LENGTH as usize);
+        let spec = PassSpec::new(32);
+        assert_eq!(
+            gen_pass_seeded(&spec, 7).unwrap(),
+            gen_pass_seeded(&spec, 7).unwrap()
+        );
+        assert_ne!(
+            gen_pass_seeded(&spec, 7).unwrap(),
+            gen_pass_seeded(&spec, 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_strength_target() {
+        let digits = PassSpec {
+            length: 8,
+            classes: vec![ClassRule::new(CharClass::Number, 0)],
+        };
+        let target = StrengthTarget {
+            min_score: Some(4),
+            min_entropy: None,
+        };
+        assert!(
+            target
+                .generate(digits.entropy_bits(), || Ok(gen_pass(&digits)?))
+                .is_err()
+        );
+
+        let target = StrengthTarget {
+            min_score: None,
+            min_entropy: Some(200.0),
+        };
+        let spec = PassSpec::new(16);
+        assert!(target.check_reachable(spec.entropy_bits()).is_err());
+
+        let target = StrengthTarget {
+            min_score: Some(4),
+            min_entropy: Some(80.0),
+        };
+        let (password, report) = target
+            .generate(spec.entropy_bits(), || Ok(gen_pass(&spec)?))
+            .unwrap();
+        assert_eq!(password.len(), 16);
+        assert_eq!(report.score, 4);
+    }
+
+    #[test]
+    fn test_gen_pass_batch_unique() {
+        let spec = PassSpec {
+            length: 2,
+            classes: vec![ClassRule::with_chars(CharClass::Custom, "ab", 0)],
+        };
+        let bits = spec.entropy_bits();
+        let all = gen_pass_batch(4, true, bits, || Ok((gen_pass(&spec)?, ()))).unwrap();
+        let distinct: HashSet<_> = all.iter().map(|(p, _)| p).collect();
+        assert_eq!(distinct.len(), 4);
+        assert!(gen_pass_batch(5, true, bits, || Ok((gen_pass(&spec)?, ()))).is_err());
+        assert_eq!(
+            gen_pass_batch(10, false, bits, || Ok((gen_pass(&spec)?, ())))
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_pass_spec_exclude() {
+        let mut spec = PassSpec::new(32);
+        spec.exclude(AMBIGUOUS);
+        spec.exclude("!@#");
+        let pool = spec.pool();
+        // '|' is not in the default pool, so only five ambiguous characters go
+        assert_eq!(pool.len(), 71 - 5 - 3);
+        for _ in 0..20 {
+            let pass = gen_pass(&spec).unwrap();
+            assert!(
+                !pass
+                    .chars()
+                    .any(|c| AMBIGUOUS.contains(c) || "!@#".contains(c))
+            );
+        }
+
+        spec.exclude(NUMBER);
+        assert!(gen_pass(&spec).is_err());
+
+        let mut custom = PassSpec {
+            length: 12,
+            classes: vec![ClassRule::with_chars(CharClass::Custom, "abcabc", 0)],
+        };
+        assert_eq!(custom.pool(), vec!['a', 'b', 'c']);
+        assert!(
+            gen_pass(&custom)
+                .unwrap()
+                .chars()
+                .all(|c| "abc".contains(c))
+        );
+        custom.exclude("abc");
+        assert!(gen_pass(&custom).is_err());
+    }
 }
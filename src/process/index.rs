@@ -0,0 +1,207 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Context;
+use csv::{ByteRecord, Position, StringRecord};
+
+use crate::{Table, XmlOptions, cli::OutputFormat, serialize_rows};
+
+const MAGIC: &[u8; 8] = b"RCLIIDX1";
+
+/// Sidecar index with the byte offset of every record of a csv file.
+///
+/// Stored next to the input as `<input>.idx`: the magic, the input length and
+/// modification time (to detect a stale index), the record count and one
+/// little endian `u64` offset per record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvIndex {
+    pub len: u64,
+    pub modified: u64,
+    pub offsets: Vec<u64>,
+}
+
+impl CsvIndex {
+    pub fn build(input: &str) -> anyhow::Result<Self> {
+        let (len, modified) = file_stamp(input)?;
+        let mut reader = csv::Reader::from_path(input).context("Failed to open input file")?;
+        let mut record = ByteRecord::new();
+        let mut offsets = Vec::new();
+        while reader.read_byte_record(&mut record)? {
+            if let Some(pos) = record.position() {
+                offsets.push(pos.byte());
+            }
+        }
+        Ok(Self {
+            len,
+            modified,
+            offsets,
+        })
+    }
+
+    pub fn path(input: &str) -> PathBuf {
+        PathBuf::from(format!("{}.idx", input))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut wtr = BufWriter::new(File::create(path).context("Failed to write index file")?);
+        wtr.write_all(MAGIC)?;
+        for n in [self.len, self.modified, self.offsets.len() as u64] {
+            wtr.write_all(&n.to_le_bytes())?;
+        }
+        for offset in &self.offsets {
+            wtr.write_all(&offset.to_le_bytes())?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(File::open(path).context("Failed to open index file")?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        anyhow::ensure!(&magic == MAGIC, "Not an rcli index file");
+        let mut next = || -> anyhow::Result<u64> {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        let (len, modified, count) = (next()?, next()?, next()?);
+        let offsets = (0..count).map(|_| next()).collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            len,
+            modified,
+            offsets,
+        })
+    }
+
+    /// The index of `input` if one exists and matches the file on disk
+    pub fn load_fresh(input: &str) -> Option<Self> {
+        let index = Self::load(&Self::path(input)).ok()?;
+        let stamp = file_stamp(input).ok()?;
+        (stamp == (index.len, index.modified)).then_some(index)
+    }
+
+    pub fn count(&self) -> usize {
+        self.offsets.len()
+    }
+}
+
+fn file_stamp(input: &str) -> anyhow::Result<(u64, u64)> {
+    let meta = std::fs::metadata(input).context("Failed to read input metadata")?;
+    let modified = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    Ok((meta.len(), modified))
+}
+
+/// Build and save the index of `input`, returns the record count
+pub fn process_csv_index(input: &str) -> anyhow::Result<usize> {
+    let index = CsvIndex::build(input)?;
+    index.save(&CsvIndex::path(input))?;
+    Ok(index.count())
+}
+
+/// Count records, from the index when it is fresh
+pub fn process_csv_count(input: &str) -> anyhow::Result<usize> {
+    if let Some(index) = CsvIndex::load_fresh(input) {
+        return Ok(index.count());
+    }
+    let mut reader = csv::Reader::from_path(input).context("Failed to open input file")?;
+    let mut record = ByteRecord::new();
+    let mut count = 0;
+    while reader.read_byte_record(&mut record)? {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Write rows `start..end` (0 based, header excluded), seeking straight to
+/// `start` when a fresh index exists
+pub fn process_csv_slice(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    start: usize,
+    end: Option<usize>,
+    xml: &XmlOptions,
+) -> anyhow::Result<()> {
+    let mut reader = csv::Reader::from_path(input).context("Failed to open input file")?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    let take = end.map_or(usize::MAX, |end| end.saturating_sub(start));
+
+    let mut skip = start;
+    if let Some(index) = CsvIndex::load_fresh(input) {
+        match index.offsets.get(start) {
+            Some(&offset) => {
+                reader.seek(position(offset, start))?;
+                skip = 0;
+            }
+            // past the last record
+            None => skip = usize::MAX,
+        }
+    }
+    let rows = reader
+        .records()
+        .skip(skip)
+        .take(take)
+        .map(|record| Ok(record?.iter().map(String::from).collect()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let content = serialize_rows(&headers, &rows, format, xml)?;
+    std::fs::write(output, content).context("Failed to write output file")?;
+    Ok(())
+}
+
+/// Read the records at the given row numbers through the index, in the
+/// order given
+pub fn read_indexed_rows(input: &str, index: &CsvIndex, rows: &[usize]) -> anyhow::Result<Table> {
+    let mut reader = csv::Reader::from_path(input).context("Failed to open input file")?;
+    let headers = reader.headers()?.iter().map(String::from).collect();
+    let mut record = StringRecord::new();
+    let mut ret = Vec::with_capacity(rows.len());
+    for &row in rows {
+        let offset = *index
+            .offsets
+            .get(row)
+            .with_context(|| format!("Row {} is out of range", row))?;
+        reader.seek(position(offset, row))?;
+        if reader.read_record(&mut record)? {
+            ret.push(record.iter().map(String::from).collect());
+        }
+    }
+    Ok((headers, ret))
+}
+
+fn position(offset: u64, row: usize) -> Position {
+    let mut pos = Position::new();
+    // record numbers count the header as record 0
+    pos.set_byte(offset).set_record(row as u64 + 1);
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_index() -> anyhow::Result<()> {
+        let input = std::env::temp_dir().join("rcli-index-test.csv");
+        std::fs::copy("./assets/juventus.csv", &input)?;
+        let input = input.to_str().unwrap();
+
+        assert_eq!(process_csv_index(input)?, 27);
+        let index = CsvIndex::load_fresh(input).expect("index is fresh");
+        assert_eq!(index, CsvIndex::load(&CsvIndex::path(input))?);
+        assert_eq!(process_csv_count(input)?, 27);
+
+        let (_, rows) = read_indexed_rows(input, &index, &[26, 0])?;
+        assert_eq!(rows[1][0], "Wojciech Szczesny");
+        assert_eq!(rows.len(), 2);
+        Ok(())
+    }
+}
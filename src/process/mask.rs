@@ -0,0 +1,98 @@
+use anyhow::Context;
+use csv::{Reader, StringRecord};
+
+use crate::cli::{MaskRule, MaskStrategy};
+
+/// hex chars kept from the keyed hash, enough to avoid collisions in a
+/// dataset while keeping pseudonyms readable
+const PSEUDONYM_LEN: usize = 16;
+const REDACTED: &str = "***";
+
+/// Mask the columns named in `rules` and write the result as csv.
+///
+/// `key` is a 32 byte blake3 key (see `rcli text generate`), required when any
+/// rule uses [`MaskStrategy::Hash`] so the same input always maps to the same
+/// pseudonym.
+pub fn process_csv_mask(
+    input: &str,
+    output: &str,
+    rules: &[MaskRule],
+    key: Option<&[u8]>,
+) -> anyhow::Result<()> {
+    let key: Option<[u8; 32]> = key
+        .map(|k| k.try_into().context("Mask key must be 32 bytes"))
+        .transpose()?;
+    if key.is_none() && rules.iter().any(|r| r.strategy == MaskStrategy::Hash) {
+        anyhow::bail!("A key file is required for the hash strategy");
+    }
+
+    let mut reader = Reader::from_path(input).context("Failed to open input file")?;
+    let headers = reader.headers()?.clone();
+    let columns = rules
+        .iter()
+        .map(|rule| {
+            headers
+                .iter()
+                .position(|h| h == rule.column)
+                .map(|idx| (idx, rule.strategy))
+                .with_context(|| format!("Column not found: {}", rule.column))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut wtr = csv::Writer::from_path(output).context("Failed to write output file")?;
+    wtr.write_record(&headers)?;
+    for result in reader.records() {
+        let record = result?;
+        let mut fields: Vec<String> = record.iter().map(String::from).collect();
+        for &(idx, strategy) in &columns {
+            if let Some(field) = fields.get_mut(idx) {
+                *field = mask_value(field, strategy, key.as_ref());
+            }
+        }
+        wtr.write_record(&StringRecord::from(fields))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn mask_value(value: &str, strategy: MaskStrategy, key: Option<&[u8; 32]>) -> String {
+    match strategy {
+        MaskStrategy::Hash => match key {
+            Some(key) => {
+                let hash = blake3::keyed_hash(key, value.as_bytes());
+                hash.to_hex()[..PSEUDONYM_LEN].to_string()
+            }
+            None => REDACTED.to_string(),
+        },
+        MaskStrategy::YearOnly => find_year(value).unwrap_or_default().to_string(),
+        MaskStrategy::Redact => REDACTED.to_string(),
+    }
+}
+
+/// first run of exactly four digits, e.g. `1990` in `Apr 18, 1990 (29)`
+fn find_year(value: &str) -> Option<&str> {
+    value
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|part| part.len() == 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_value() {
+        let key = [7u8; 32];
+        let a = mask_value("Gianluigi Buffon", MaskStrategy::Hash, Some(&key));
+        let b = mask_value("Gianluigi Buffon", MaskStrategy::Hash, Some(&key));
+        let c = mask_value("Gianluigi Buffon", MaskStrategy::Hash, Some(&[8u8; 32]));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), PSEUDONYM_LEN);
+        assert_eq!(
+            mask_value("Apr 18, 1990 (29)", MaskStrategy::YearOnly, None),
+            "1990"
+        );
+        assert_eq!(mask_value("Italy", MaskStrategy::Redact, None), REDACTED);
+    }
+}
@@ -1,10 +1,60 @@
+mod audit;
 mod base64;
+mod batch;
 mod csv;
+mod date;
+mod derive;
+mod expr;
+mod fake;
 mod gen_pass;
 mod http_serve;
+mod index;
+mod mask;
+mod passphrase;
+mod policy;
+mod pronounceable;
+mod pwned;
+mod reshape;
+mod sample;
+mod schema;
 mod text;
+mod token;
+mod transform;
+mod xml;
+pub use audit::{
+    AuditEntry, audit_password, mask_password, process_password_audit, render_audit_table,
+};
 pub use base64::{process_base64_decode, process_base64_encode};
-pub use csv::{Record, process_csv};
-pub use gen_pass::gen_pass;
+pub use batch::{
+    BatchSummary, expand_inputs, is_glob, plan_outputs, process_csv_batch, render_output,
+};
+pub use csv::{
+    ConvertStats, CsvConvertOptions, Record, Table, process_csv, read_rows, sanitize_formula,
+    serialize_rows,
+};
+pub use date::{DateOptions, check_date_format, format_date, parse_date};
+pub use derive::{derive_password, derive_rng, secret_fingerprint};
+pub use expr::{ComputedColumn, Expr, Op, Value};
+pub use fake::{FakeColumn, FakeKind, FakeSchema, age_at, gen_fake_rows, process_csv_fake};
+pub use gen_pass::{
+    AMBIGUOUS, CharClass, ClassRule, CrackTime, GenPassError, MAX_LENGTH, PassSpec, StrengthReport,
+    StrengthTarget, gen_pass, gen_pass_batch, gen_pass_with,
+};
 pub use http_serve::process_http_serve;
+pub use index::{
+    CsvIndex, process_csv_count, process_csv_index, process_csv_slice, read_indexed_rows,
+};
+pub use mask::{mask_value, process_csv_mask};
+pub use passphrase::{PassphraseSpec, Wordlist, gen_passphrase};
+pub use policy::{PasswordPolicy, Violation};
+pub use pronounceable::{PronounceableSpec, gen_pronounceable, gen_pronounceable_with};
+pub use pwned::{PwnedDb, PwnedEntry, process_password_pwned};
+pub use reshape::{Melt, Pivot, process_csv_melt, process_csv_pivot};
+pub use sample::{SampleMode, process_csv_sample, sample_rows};
+pub use schema::{ColumnSchema, ColumnType, CsvSchema, infer_column, process_csv_infer_schema};
 pub use text::{process_text_key_generate, process_text_sign, process_text_verify};
+pub use token::{
+    Monotonic, NANOID_ALPHABET, gen_nanoid, gen_secret, gen_ulid, gen_uuid_v4, gen_uuid_v7,
+};
+pub use transform::RowTransform;
+pub use xml::{XmlOptions, XmlWriter, process_xml, read_xml, sanitize_tag};
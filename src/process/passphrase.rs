@@ -0,0 +1,155 @@
+use anyhow::Context;
+use rand::{Rng, rngs::OsRng, seq::SliceRandom};
+
+/// 7776 words (4-8 letters), the size of a five-dice diceware list. A stand-in drawn
+/// from a word frequency list, not the EFF large wordlist, see `assets/README.md`.
+const BUILTIN_WORDLIST: &str = include_str!("../../assets/wordlist.txt");
+
+#[derive(Debug, Clone)]
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_WORDLIST).expect("builtin wordlist is valid")
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read wordlist {}", path))?;
+        Self::parse(&text)
+    }
+
+    /// One word per line. Diceware style lines such as `11111\tabacus` keep only the
+    /// word, and duplicates are dropped so every word is equally likely.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut words: Vec<String> = text
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .map(str::to_string)
+            .collect();
+        words.sort();
+        words.dedup();
+        anyhow::ensure!(
+            words.len() >= 2,
+            "Wordlist needs at least two distinct words"
+        );
+        Ok(Self { words })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassphraseSpec {
+    pub words: usize,
+    pub separator: String,
+    pub capitalize: bool,
+    pub add_digit: bool,
+}
+
+impl Default for PassphraseSpec {
+    fn default() -> Self {
+        Self {
+            words: 6,
+            separator: "-".to_string(),
+            capitalize: false,
+            add_digit: false,
+        }
+    }
+}
+
+impl PassphraseSpec {
+    /// Bits of entropy against an attacker who knows the wordlist and these settings.
+    /// Capitalizing every word is predictable and adds nothing; the extra digit adds its
+    /// value and the word it is attached to.
+    pub fn entropy_bits(&self, list: &Wordlist) -> f64 {
+        let mut bits = self.words as f64 * (list.len() as f64).log2();
+        if self.add_digit {
+            bits += (10.0 * self.words as f64).log2();
+        }
+        bits
+    }
+}
+
+pub fn gen_passphrase(list: &Wordlist, spec: &PassphraseSpec) -> anyhow::Result<String> {
+    anyhow::ensure!(spec.words > 0, "A passphrase needs at least one word");
+    let mut rng = OsRng;
+    let mut words = Vec::with_capacity(spec.words);
+    for _ in 0..spec.words {
+        let word = list
+            .words
+            .choose(&mut rng)
+            .context("The wordlist is empty")?;
+        words.push(word.clone());
+    }
+    if spec.capitalize {
+        for word in &mut words {
+            if let Some(first) = word.chars().next() {
+                let rest = &word[first.len_utf8()..];
+                *word = first.to_uppercase().chain(rest.chars()).collect();
+            }
+        }
+    }
+    if spec.add_digit {
+        let i = rng.gen_range(0..words.len());
+        words[i].push(char::from(b'0' + rng.gen_range(0..10)));
+    }
+
+    Ok(words.join(&spec.separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen_passphrase() {
+        let list = Wordlist::builtin();
+        assert_eq!(list.len(), 7776);
+        let spec = PassphraseSpec {
+            capitalize: true,
+            add_digit: true,
+            ..Default::default()
+        };
+        let phrase = gen_passphrase(&list, &spec).unwrap();
+        let words: Vec<&str> = phrase.split('-').collect();
+        assert_eq!(words.len(), 6);
+        assert!(
+            words
+                .iter()
+                .all(|w| w.starts_with(|c: char| c.is_uppercase()))
+        );
+        assert_eq!(phrase.chars().filter(char::is_ascii_digit).count(), 1);
+        let bits = spec.entropy_bits(&list);
+        assert!((bits - (6.0 * 7776f64.log2() + 60f64.log2())).abs() < 1e-9);
+    }
+
+    // Fails until the EFF long list replaces the stand-in in assets/wordlist.txt, which
+    // could not be fetched when this was written; run with `cargo test -- --ignored`
+    #[test]
+    #[ignore = "the builtin list is still a stand-in for the EFF long list"]
+    fn test_builtin_is_eff_large_wordlist() {
+        let list = Wordlist::builtin();
+        assert_eq!(list.len(), 7776);
+        assert_eq!(list.words.first().map(String::as_str), Some("abacus"));
+        assert_eq!(list.words.last().map(String::as_str), Some("zoom"));
+        for word in ["abdomen", "abdominal"] {
+            assert!(list.words.iter().any(|w| w == word), "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_wordlist_parse() {
+        let list = Wordlist::parse("11111\tabacus\n11112\tabdomen\n\nabacus\n").unwrap();
+        assert_eq!(list.words, vec!["abacus", "abdomen"]);
+        assert!(Wordlist::parse("only").is_err());
+    }
+}
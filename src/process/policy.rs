@@ -0,0 +1,299 @@
+use std::fmt;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{CharClass, PassSpec, cli::PolicyPreset, process::gen_pass::MAX_ATTEMPTS};
+
+/// Rules a password must follow, from a preset or a YAML policy file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: Option<usize>,
+    /// Classes that must appear at least once.
+    pub required: Vec<CharClass>,
+    /// Characters that must never appear.
+    pub forbidden: String,
+    /// Longest allowed run of one repeated character.
+    pub max_repeat: Option<usize>,
+    /// Substrings that must not appear, compared case-insensitively.
+    pub banned: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.message)
+    }
+}
+
+impl PolicyPreset {
+    pub fn policy(self) -> PasswordPolicy {
+        match self {
+            // SP 800-63B: length over composition, no silly repeats or obvious words
+            PolicyPreset::Nist => PasswordPolicy {
+                min_length: 8,
+                max_length: Some(64),
+                max_repeat: Some(3),
+                banned: vec!["password".to_string()],
+                ..Default::default()
+            },
+            // PCI DSS 4.0 8.3.6: at least 12 characters with letters and digits
+            PolicyPreset::Pci => PasswordPolicy {
+                min_length: 12,
+                required: vec![CharClass::Lower, CharClass::Number],
+                ..Default::default()
+            },
+            // Old systems with fixed 8 character fields and fussy input handling
+            PolicyPreset::Legacy8 => PasswordPolicy {
+                min_length: 8,
+                max_length: Some(8),
+                required: CharClass::ALL.to_vec(),
+                forbidden: " \"'`\\<>".to_string(),
+                max_repeat: Some(2),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read policy file {}", path))?;
+        let policy: Self = serde_yaml::from_str(&text).context("Failed to parse policy file")?;
+        anyhow::ensure!(
+            !policy.required.contains(&CharClass::Custom),
+            "A policy cannot require the custom class"
+        );
+        Ok(policy)
+    }
+
+    /// Fit `spec` to the policy: pick a length in range, turn on the required classes and
+    /// drop forbidden characters. `length` is the length the user asked for, if any.
+    pub fn apply(&self, spec: &mut PassSpec, length: Option<u16>) -> anyhow::Result<()> {
+        spec.length = self.fit_length(spec.length, length)?;
+        for class in &self.required {
+            match spec.classes.iter_mut().find(|r| r.class == *class) {
+                Some(rule) => rule.min = rule.min.max(1),
+                None => anyhow::bail!(
+                    "The policy requires {} characters, which are disabled",
+                    class.name()
+                ),
+            }
+        }
+        spec.exclude(&self.forbidden);
+        Ok(())
+    }
+
+    /// The requested `length` if the policy allows it, otherwise `current` clamped into
+    /// the policy's range.
+    pub fn fit_length(&self, current: u16, length: Option<u16>) -> anyhow::Result<u16> {
+        let max = self.max_length.unwrap_or(usize::MAX);
+        anyhow::ensure!(
+            self.min_length <= max,
+            "Policy minimum length {} is above its maximum {}",
+            self.min_length,
+            max
+        );
+        Ok(match length {
+            Some(length) => {
+                anyhow::ensure!(
+                    (self.min_length..=max).contains(&(length as usize)),
+                    "Length {} is outside the policy range {}..={}",
+                    length,
+                    self.min_length,
+                    max
+                );
+                length
+            }
+            None => (current as usize).clamp(self.min_length, max).try_into()?,
+        })
+    }
+
+    /// Draw from `generate` until a password passes every rule.
+    pub fn generate(
+        &self,
+        mut generate: impl FnMut() -> anyhow::Result<String>,
+    ) -> anyhow::Result<String> {
+        let mut violations = Vec::new();
+        for _ in 0..MAX_ATTEMPTS {
+            let password = generate()?;
+            violations = self.check(&password);
+            if violations.is_empty() {
+                return Ok(password);
+            }
+        }
+        anyhow::bail!(
+            "No password satisfied the policy in {} attempts, last one broke: {}",
+            MAX_ATTEMPTS,
+            violations
+                .iter()
+                .map(|v| v.rule)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Every rule `password` breaks, empty when it complies.
+    pub fn check(&self, password: &str) -> Vec<Violation> {
+        self.violations(password, true)
+    }
+
+    /// Like [`PasswordPolicy::check`], but the messages never quote characters or
+    /// words found in the password.
+    pub fn check_masked(&self, password: &str) -> Vec<Violation> {
+        self.violations(password, false)
+    }
+
+    fn violations(&self, password: &str, reveal: bool) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let len = password.chars().count();
+        if len < self.min_length {
+            violations.push(Violation {
+                rule: "min-length",
+                message: format!("{} characters, at least {} required", len, self.min_length),
+            });
+        }
+        if let Some(max) = self.max_length.filter(|max| len > *max) {
+            violations.push(Violation {
+                rule: "max-length",
+                message: format!("{} characters, at most {} allowed", len, max),
+            });
+        }
+        for class in &self.required {
+            if !password.chars().any(|c| class.matches(c)) {
+                violations.push(Violation {
+                    rule: "required-class",
+                    message: format!("no {} characters", class.name()),
+                });
+            }
+        }
+        let mut forbidden: Vec<char> = password
+            .chars()
+            .filter(|c| self.forbidden.contains(*c))
+            .collect();
+        forbidden.sort_unstable();
+        forbidden.dedup();
+        if !forbidden.is_empty() {
+            violations.push(Violation {
+                rule: "forbidden",
+                message: match reveal {
+                    true => format!(
+                        "contains forbidden characters {}",
+                        forbidden.into_iter().collect::<String>()
+                    ),
+                    false => format!("contains {} forbidden characters", forbidden.len()),
+                },
+            });
+        }
+        if let Some(max) = self.max_repeat {
+            let (c, run) = longest_run(password);
+            if run > max {
+                violations.push(Violation {
+                    rule: "max-repeat",
+                    message: match reveal {
+                        true => format!("'{}' repeats {} times, at most {} allowed", c, run, max),
+                        false => {
+                            format!("a character repeats {} times, at most {} allowed", run, max)
+                        }
+                    },
+                });
+            }
+        }
+        let lower = password.to_lowercase();
+        for banned in &self.banned {
+            if !banned.is_empty() && lower.contains(&banned.to_lowercase()) {
+                violations.push(Violation {
+                    rule: "banned",
+                    message: match reveal {
+                        true => format!("contains '{}'", banned),
+                        false => "contains a banned word".to_string(),
+                    },
+                });
+            }
+        }
+        violations
+    }
+}
+
+fn longest_run(password: &str) -> (char, usize) {
+    let mut best = (' ', 0);
+    let mut current = (' ', 0);
+    for c in password.chars() {
+        current = match current {
+            (prev, n) if prev == c && n > 0 => (c, n + 1),
+            _ => (c, 1),
+        };
+        if current.1 > best.1 {
+            best = current;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen_pass;
+
+    #[test]
+    fn test_policy_check() {
+        let policy = PasswordPolicy {
+            min_length: 10,
+            max_length: Some(12),
+            required: vec![CharClass::Upper, CharClass::Number],
+            forbidden: "'".to_string(),
+            max_repeat: Some(2),
+            banned: vec!["Alice".to_string()],
+        };
+        let rules: Vec<&str> = policy.check("aaalice'").iter().map(|v| v.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                "min-length",
+                "required-class",
+                "required-class",
+                "forbidden",
+                "max-repeat",
+                "banned"
+            ]
+        );
+        assert!(policy.check("Xy7!abcdef").is_empty());
+    }
+
+    #[test]
+    fn test_policy_presets() {
+        for preset in [PolicyPreset::Nist, PolicyPreset::Pci, PolicyPreset::Legacy8] {
+            let policy = preset.policy();
+            let mut spec = PassSpec::default();
+            policy.apply(&mut spec, None).unwrap();
+            let password = policy.generate(|| Ok(gen_pass(&spec)?)).unwrap();
+            assert!(policy.check(&password).is_empty(), "{:?}", preset);
+        }
+        let mut spec = PassSpec::default();
+        assert!(
+            PolicyPreset::Legacy8
+                .policy()
+                .apply(&mut spec, Some(16))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_policy_file() {
+        let policy: PasswordPolicy = serde_yaml::from_str(
+            "min_length: 12\nrequired: [upper, number]\nbanned: [acme]\nmax_repeat: 2\n",
+        )
+        .unwrap();
+        assert_eq!(policy.required, vec![CharClass::Upper, CharClass::Number]);
+        assert_eq!(policy.max_length, None);
+        assert!(serde_yaml::from_str::<PasswordPolicy>("min_len: 3").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use rand::{CryptoRng, RngCore, rngs::OsRng, seq::SliceRandom};
+
+use crate::{CharClass, GenPassError, MAX_LENGTH, PasswordPolicy};
+
+// No c, q, w, x, y or l: they sound alike or need spelling out over the phone
+const CONSONANTS: &str = "bdfghjkmnprstvz";
+const VOWELS: &str = "aeiou";
+
+/// Consonant-vowel syllables with an optional block of digits and symbols at the end,
+/// e.g. `Dabokefu42!`.
+#[derive(Debug, Clone)]
+pub struct PronounceableSpec {
+    pub length: u16,
+    /// Syllables that start with a capital letter.
+    pub upper: usize,
+    pub digits: usize,
+    pub symbols: usize,
+    pub symbol_chars: Vec<char>,
+    consonants: Vec<char>,
+    vowels: Vec<char>,
+    digit_chars: Vec<char>,
+}
+
+impl PronounceableSpec {
+    pub fn new(length: u16) -> Self {
+        Self {
+            length,
+            upper: 0,
+            digits: 0,
+            symbols: 0,
+            symbol_chars: CharClass::Symbol.default_chars().chars().collect(),
+            consonants: CONSONANTS.chars().collect(),
+            vowels: VOWELS.chars().collect(),
+            digit_chars: CharClass::Number.default_chars().chars().collect(),
+        }
+    }
+
+    /// Drop `chars` from every pool. A consonant goes if either case is excluded, since
+    /// any syllable may be capitalized.
+    pub fn exclude(&mut self, chars: &str) {
+        self.consonants
+            .retain(|c| !chars.contains(*c) && !chars.contains(c.to_ascii_uppercase()));
+        self.vowels.retain(|c| !chars.contains(*c));
+        self.digit_chars.retain(|c| !chars.contains(*c));
+        self.symbol_chars.retain(|c| !chars.contains(*c));
+    }
+
+    /// Fit the spec to `policy`: its length range, one character of each required class
+    /// and none of its forbidden characters.
+    pub fn apply_policy(
+        &mut self,
+        policy: &PasswordPolicy,
+        length: Option<u16>,
+    ) -> anyhow::Result<()> {
+        self.length = policy.fit_length(self.length, length)?;
+        for class in &policy.required {
+            match class {
+                CharClass::Upper => self.upper = self.upper.max(1),
+                CharClass::Number => self.digits = self.digits.max(1),
+                CharClass::Symbol => self.symbols = self.symbols.max(1),
+                CharClass::Lower | CharClass::Custom => {}
+            }
+        }
+        self.exclude(&policy.forbidden);
+        Ok(())
+    }
+
+    fn letters(&self) -> usize {
+        (self.length as usize).saturating_sub(self.digits + self.symbols)
+    }
+
+    fn syllables(&self) -> usize {
+        self.letters().div_ceil(2)
+    }
+
+    /// Bits of entropy from the choices actually made: each letter from its pool, which
+    /// syllables are capitalized, the trailing digits and symbols and their order. Much
+    /// lower than a uniform password of the same length. With a policy this is an upper
+    /// bound, since passwords that break it are drawn again rather than counted.
+    pub fn entropy_bits(&self) -> f64 {
+        let letters = self.letters();
+        pool_bits(letters.div_ceil(2), &self.consonants)
+            + pool_bits(letters / 2, &self.vowels)
+            + log2_binomial(self.syllables(), self.upper)
+            + pool_bits(self.digits, &self.digit_chars)
+            + pool_bits(self.symbols, &self.symbol_chars)
+            + log2_binomial(self.digits + self.symbols, self.digits)
+    }
+
+    fn validate(&self) -> Result<(), GenPassError> {
+        if !(1..=MAX_LENGTH).contains(&self.length) {
+            return Err(GenPassError::Length(self.length));
+        }
+        if self.letters() < 2 {
+            return Err(GenPassError::NoSyllables {
+                length: self.length,
+                digits: self.digits,
+                symbols: self.symbols,
+            });
+        }
+        if self.upper > self.syllables() {
+            return Err(GenPassError::TooManyCapitals {
+                syllables: self.syllables(),
+                upper: self.upper,
+            });
+        }
+        for (class, pool, min) in [
+            ("consonant", &self.consonants, self.syllables()),
+            ("vowel", &self.vowels, self.letters() / 2),
+            (CharClass::Number.name(), &self.digit_chars, self.digits),
+            (CharClass::Symbol.name(), &self.symbol_chars, self.symbols),
+        ] {
+            if min > 0 && pool.is_empty() {
+                return Err(GenPassError::ClassExhausted { class, min });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for PronounceableSpec {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+pub fn gen_pronounceable(spec: &PronounceableSpec) -> Result<String, GenPassError> {
+    gen_pronounceable_with(spec, &mut OsRng)
+}
+
+pub fn gen_pronounceable_with<R: RngCore + CryptoRng + ?Sized>(
+    spec: &PronounceableSpec,
+    rng: &mut R,
+) -> Result<String, GenPassError> {
+    spec.validate()?;
+    let mut letters = Vec::with_capacity(spec.length as usize);
+    for i in 0..spec.letters() {
+        let pool = match i % 2 {
+            0 => &spec.consonants,
+            _ => &spec.vowels,
+        };
+        letters.push(*pool.choose(rng).ok_or(GenPassError::EmptyPool)?);
+    }
+    let starts: Vec<usize> = (0..spec.syllables()).map(|i| i * 2).collect();
+    for &i in starts.choose_multiple(rng, spec.upper) {
+        letters[i] = letters[i].to_ascii_uppercase();
+    }
+
+    let mut tail = Vec::with_capacity(spec.digits + spec.symbols);
+    for (pool, count) in [
+        (&spec.digit_chars, spec.digits),
+        (&spec.symbol_chars, spec.symbols),
+    ] {
+        for _ in 0..count {
+            tail.push(*pool.choose(rng).ok_or(GenPassError::EmptyPool)?);
+        }
+    }
+    tail.shuffle(rng);
+    letters.extend(tail);
+    Ok(letters.into_iter().collect())
+}
+
+fn pool_bits(count: usize, pool: &[char]) -> f64 {
+    match count {
+        0 => 0.0,
+        _ => count as f64 * (pool.len() as f64).log2(),
+    }
+}
+
+fn log2_binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    let k = k.min(n - k);
+    (0..k)
+        .map(|i| ((n - i) as f64).log2() - ((i + 1) as f64).log2())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::PolicyPreset;
+
+    #[test]
+    fn test_gen_pronounceable() {
+        let mut spec = PronounceableSpec::new(12);
+        spec.upper = 1;
+        spec.digits = 2;
+        let mut rng = StdRng::seed_from_u64(7);
+        let password = gen_pronounceable_with(&spec, &mut rng).unwrap();
+        assert_eq!(password.len(), 12);
+        let (letters, digits) = password.split_at(10);
+        assert!(digits.chars().all(|c| c.is_ascii_digit()));
+        for (i, c) in letters.to_ascii_lowercase().chars().enumerate() {
+            let pool = if i % 2 == 0 { CONSONANTS } else { VOWELS };
+            assert!(pool.contains(c), "{}", password);
+        }
+        assert_eq!(letters.chars().filter(char::is_ascii_uppercase).count(), 1);
+
+        // 5 consonants, 5 vowels, 1 of 5 syllables capitalized, 2 digits
+        let expected = 5.0 * 15f64.log2() + 5.0 * 5f64.log2() + 5f64.log2() + 2.0 * 10f64.log2();
+        assert!((spec.entropy_bits() - expected).abs() < 1e-9);
+        assert!(spec.entropy_bits() < crate::PassSpec::new(12).entropy_bits());
+    }
+
+    #[test]
+    fn test_pronounceable_policy() {
+        let policy = PolicyPreset::Legacy8.policy();
+        let mut spec = PronounceableSpec::default();
+        spec.apply_policy(&policy, None).unwrap();
+        assert_eq!(spec.length, 8);
+        let password = policy.generate(|| Ok(gen_pronounceable(&spec)?)).unwrap();
+        assert!(policy.check(&password).is_empty(), "{}", password);
+
+        spec.digits = 7;
+        assert_eq!(
+            gen_pronounceable(&spec),
+            Err(GenPassError::NoSyllables {
+                length: 8,
+                digits: 7,
+                symbols: 1
+            })
+        );
+        spec.digits = 1;
+        spec.upper = 4;
+        assert_eq!(
+            gen_pronounceable(&spec),
+            Err(GenPassError::TooManyCapitals {
+                syllables: 3,
+                upper: 4
+            })
+        );
+        spec.upper = 1;
+        spec.exclude(VOWELS);
+        assert_eq!(
+            gen_pronounceable(&spec),
+            Err(GenPassError::ClassExhausted {
+                class: "vowel",
+                min: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::{mask_password, process::gen_pass::MAX_ATTEMPTS};
+
+/// A local copy of the Have I Been Pwned password list, in the SHA-1 "ordered by hash"
+/// format: one `HASH:COUNT` line per password, sorted by hash.
+pub struct PwnedDb {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+impl PwnedDb {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open pwned database {}", path))?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    /// How often `password` appears in breaches, 0 when it is not listed.
+    pub fn count(&mut self, password: &str) -> anyhow::Result<u64> {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        self.lookup(&hash)
+    }
+
+    /// Binary search over byte offsets; each probe reads the first full line at or after
+    /// the midpoint, so the file never has to be loaded or indexed.
+    fn lookup(&mut self, hash: &str) -> anyhow::Result<u64> {
+        let (mut lo, mut hi) = (0, self.len);
+        let mut line = String::new();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let start = self.line_start(mid)?;
+            if start >= hi {
+                hi = mid;
+                continue;
+            }
+            line.clear();
+            let read = self.reader.read_line(&mut line)?;
+            let (entry, count) = line
+                .trim_end()
+                .split_once(':')
+                .with_context(|| format!("Malformed pwned database line at byte {}", start))?;
+            match entry.to_ascii_uppercase().as_str().cmp(hash) {
+                std::cmp::Ordering::Equal => {
+                    return count
+                        .parse()
+                        .with_context(|| format!("Invalid count in pwned database: {}", count));
+                }
+                std::cmp::Ordering::Less => lo = start + read as u64,
+                std::cmp::Ordering::Greater => hi = mid,
+            }
+        }
+        Ok(0)
+    }
+
+    /// Position the reader at the first line starting at or after `pos`.
+    fn line_start(&mut self, pos: u64) -> anyhow::Result<u64> {
+        if pos == 0 {
+            self.reader.seek(SeekFrom::Start(0))?;
+            return Ok(0);
+        }
+        self.reader.seek(SeekFrom::Start(pos - 1))?;
+        let mut skipped = Vec::new();
+        let n = self.reader.read_until(b'\n', &mut skipped)?;
+        Ok(pos - 1 + n as u64)
+    }
+
+    /// Draw from `generate` until a password is not in the breach list.
+    pub fn generate(
+        &mut self,
+        mut generate: impl FnMut() -> anyhow::Result<String>,
+    ) -> anyhow::Result<String> {
+        for _ in 0..MAX_ATTEMPTS {
+            let password = generate()?;
+            if self.count(&password)? == 0 {
+                return Ok(password);
+            }
+        }
+        anyhow::bail!(
+            "Every one of {} generated passwords was found in the breach list",
+            MAX_ATTEMPTS
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PwnedEntry {
+    pub line: usize,
+    pub password: String,
+    pub count: u64,
+}
+
+/// Look up every non-empty line of `reader`; passwords are masked unless `reveal` is set.
+pub fn process_password_pwned(
+    reader: &mut dyn Read,
+    db: &mut PwnedDb,
+    reveal: bool,
+) -> anyhow::Result<Vec<PwnedEntry>> {
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let password = line.trim_end_matches('\r');
+        if password.is_empty() {
+            continue;
+        }
+        entries.push(PwnedEntry {
+            line: i + 1,
+            password: match reveal {
+                true => password.to_string(),
+                false => mask_password(password),
+            },
+            count: db.count(password)?,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pwned_db_lookup() -> anyhow::Result<()> {
+        let known = ["password", "123456", "hunter2", "letmein", "qwerty"];
+        let mut lines: Vec<String> = known
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let hash = hex::encode_upper(Sha1::digest(p.as_bytes()));
+                format!("{}:{}\r\n", hash, (i + 1) * 1000)
+            })
+            .collect();
+        lines.sort();
+        let path = std::env::temp_dir().join("rcli-pwned-test.txt");
+        std::fs::write(&path, lines.concat())?;
+
+        let mut db = PwnedDb::open(path.to_str().unwrap())?;
+        for (i, p) in known.iter().enumerate() {
+            assert_eq!(db.count(p)?, (i as u64 + 1) * 1000, "{}", p);
+        }
+        assert_eq!(db.count("correct horse battery staple")?, 0);
+        assert_eq!(db.count("")?, 0);
+
+        let entries =
+            process_password_pwned(&mut "hunter2\nnot-listed\n".as_bytes(), &mut db, false)?;
+        assert_eq!(entries[0].count, 3000);
+        assert_eq!(entries[0].password, "h*****2");
+        assert_eq!(entries[1].count, 0);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
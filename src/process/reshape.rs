@@ -0,0 +1,242 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Context;
+
+use crate::{
+    XmlOptions,
+    cli::{Aggregation, OutputFormat},
+    read_rows, serialize_rows,
+};
+
+/// Long to wide reshaping, one output row per distinct `index` and one column
+/// per distinct value of `columns`
+#[derive(Debug, Clone)]
+pub struct Pivot {
+    pub index: Vec<String>,
+    pub columns: String,
+    /// column aggregated into each cell, optional for `count`
+    pub values: Option<String>,
+    pub agg: Aggregation,
+}
+
+/// Wide to long reshaping, every `vars` column becomes its own row next to
+/// the `id` columns
+#[derive(Debug, Clone)]
+pub struct Melt {
+    pub id: Vec<String>,
+    /// columns to unpivot, all non id columns when empty
+    pub vars: Vec<String>,
+    pub var_name: String,
+    pub value_name: String,
+}
+
+pub fn process_csv_pivot(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    pivot: &Pivot,
+    xml: &XmlOptions,
+) -> anyhow::Result<()> {
+    let (headers, rows) = read_rows(input)?;
+    let (headers, rows) = pivot.apply(&headers, &rows)?;
+    let content = serialize_rows(&headers, &rows, format, xml)?;
+    std::fs::write(output, content).context("Failed to write output file")?;
+    Ok(())
+}
+
+pub fn process_csv_melt(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    melt: &Melt,
+    xml: &XmlOptions,
+) -> anyhow::Result<()> {
+    let (headers, rows) = read_rows(input)?;
+    let (headers, rows) = melt.apply(&headers, &rows)?;
+    let content = serialize_rows(&headers, &rows, format, xml)?;
+    std::fs::write(output, content).context("Failed to write output file")?;
+    Ok(())
+}
+
+impl Pivot {
+    pub fn apply(
+        &self,
+        headers: &[String],
+        rows: &[Vec<String>],
+    ) -> anyhow::Result<(Vec<String>, Vec<Vec<String>>)> {
+        let index = self
+            .index
+            .iter()
+            .map(|col| column_index(headers, col))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let columns = column_index(headers, &self.columns)?;
+        let values = match (&self.values, self.agg) {
+            (Some(col), _) => Some(column_index(headers, col)?),
+            (None, Aggregation::Count) => None,
+            (None, agg) => anyhow::bail!("--values is required for {}", agg),
+        };
+
+        // index keys in first appearance order, pivoted columns sorted
+        let mut keys: Vec<Vec<String>> = Vec::new();
+        let mut cells: HashMap<(usize, String), Vec<String>> = HashMap::new();
+        let mut pivoted: BTreeSet<String> = BTreeSet::new();
+        let mut key_pos: HashMap<Vec<String>, usize> = HashMap::new();
+        for row in rows {
+            let key: Vec<String> = index.iter().map(|&i| field(row, i)).collect();
+            let pos = *key_pos.entry(key.clone()).or_insert_with(|| {
+                keys.push(key);
+                keys.len() - 1
+            });
+            let column = field(row, columns);
+            let value = values.map(|i| field(row, i)).unwrap_or_default();
+            cells.entry((pos, column.clone())).or_default().push(value);
+            pivoted.insert(column);
+        }
+
+        let mut out_headers = self.index.clone();
+        out_headers.extend(pivoted.iter().cloned());
+        let mut out = Vec::with_capacity(keys.len());
+        for (pos, key) in keys.into_iter().enumerate() {
+            let mut row = key;
+            for column in &pivoted {
+                let values = cells.remove(&(pos, column.clone())).unwrap_or_default();
+                row.push(self.agg.apply(&values)?);
+            }
+            out.push(row);
+        }
+        Ok((out_headers, out))
+    }
+}
+
+impl Aggregation {
+    pub fn apply(&self, values: &[String]) -> anyhow::Result<String> {
+        if let Aggregation::Count = self {
+            return Ok(values.len().to_string());
+        }
+        if values.is_empty() {
+            return Ok(String::new());
+        }
+        if let Aggregation::First = self {
+            return Ok(values[0].clone());
+        }
+        let nums = values
+            .iter()
+            .map(|v| {
+                v.trim()
+                    .parse::<f64>()
+                    .with_context(|| format!("Cannot {} non numeric value {:?}", self, v))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ret = match self {
+            Aggregation::Sum => nums.iter().sum(),
+            Aggregation::Mean => nums.iter().sum::<f64>() / nums.len() as f64,
+            Aggregation::Min => nums.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregation::Max => nums.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Count | Aggregation::First => unreachable!(),
+        };
+        Ok(crate::Value::Num(ret).to_string())
+    }
+}
+
+impl Melt {
+    pub fn apply(
+        &self,
+        headers: &[String],
+        rows: &[Vec<String>],
+    ) -> anyhow::Result<(Vec<String>, Vec<Vec<String>>)> {
+        let id = self
+            .id
+            .iter()
+            .map(|col| column_index(headers, col))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let vars = match self.vars.is_empty() {
+            true => (0..headers.len()).filter(|i| !id.contains(i)).collect(),
+            false => self
+                .vars
+                .iter()
+                .map(|col| column_index(headers, col))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        };
+
+        let mut out_headers = self.id.clone();
+        out_headers.push(self.var_name.clone());
+        out_headers.push(self.value_name.clone());
+        let mut out = Vec::with_capacity(rows.len() * vars.len());
+        for row in rows {
+            for &var in &vars {
+                let mut melted: Vec<String> = id.iter().map(|&i| field(row, i)).collect();
+                melted.push(headers[var].clone());
+                melted.push(field(row, var));
+                out.push(melted);
+            }
+        }
+        Ok((out_headers, out))
+    }
+}
+
+fn field(row: &[String], idx: usize) -> String {
+    row.get(idx).cloned().unwrap_or_default()
+}
+
+fn column_index(headers: &[String], name: &str) -> anyhow::Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == name)
+        .with_context(|| format!("Column not found: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = ["Name", "Position", "Nationality", "Kit Number"]
+            .map(String::from)
+            .to_vec();
+        let rows = [
+            ["Buffon", "Goalkeeper", "Italy", "77"],
+            ["Perin", "Goalkeeper", "Italy", "37"],
+            ["Szczesny", "Goalkeeper", "Poland", "1"],
+            ["Bonucci", "Defender", "Italy", "19"],
+        ]
+        .map(|r| r.map(String::from).to_vec())
+        .to_vec();
+        (headers, rows)
+    }
+
+    #[test]
+    fn test_pivot() -> anyhow::Result<()> {
+        let (headers, rows) = table();
+        let mut pivot = Pivot {
+            index: vec!["Nationality".into()],
+            columns: "Position".into(),
+            values: None,
+            agg: Aggregation::Count,
+        };
+        let (h, r) = pivot.apply(&headers, &rows)?;
+        assert_eq!(h, vec!["Nationality", "Defender", "Goalkeeper"]);
+        assert_eq!(r, vec![vec!["Italy", "1", "2"], vec!["Poland", "0", "1"]]);
+
+        pivot.values = Some("Kit Number".into());
+        pivot.agg = Aggregation::Sum;
+        let (_, r) = pivot.apply(&headers, &rows)?;
+        assert_eq!(r, vec![vec!["Italy", "19", "114"], vec!["Poland", "", "1"]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_melt() -> anyhow::Result<()> {
+        let (headers, rows) = table();
+        let melt = Melt {
+            id: vec!["Name".into()],
+            vars: vec!["Position".into(), "Kit Number".into()],
+            var_name: "variable".into(),
+            value_name: "value".into(),
+        };
+        let (h, r) = melt.apply(&headers, &rows)?;
+        assert_eq!(h, vec!["Name", "variable", "value"]);
+        assert_eq!(r.len(), 8);
+        assert_eq!(r[1], vec!["Buffon", "Kit Number", "77"]);
+        Ok(())
+    }
+}
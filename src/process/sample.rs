@@ -0,0 +1,292 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Context;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::index};
+
+use crate::{
+    CsvIndex, Table, XmlOptions, cli::OutputFormat, get_reader, read_indexed_rows, serialize_rows,
+};
+
+#[derive(Debug, Clone)]
+pub enum SampleMode {
+    /// uniform reservoir sample of `n` rows
+    Random {
+        n: usize,
+    },
+    /// `n` rows keeping the share of each value of `column`
+    Stratified {
+        n: usize,
+        column: String,
+    },
+    Head(usize),
+    Tail(usize),
+}
+
+/// A row kept by the sampler with its position in the input
+type Indexed = (usize, Vec<String>);
+
+/// Sample rows from `input` (a file or `-` for stdin) in one pass, sampled rows
+/// keep their input order. Random and tail samples of a file with a fresh
+/// index (see `rcli csv index`) read only the chosen rows.
+pub fn process_csv_sample(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    mode: &SampleMode,
+    seed: Option<u64>,
+    xml: &XmlOptions,
+) -> anyhow::Result<()> {
+    if let Some((headers, rows)) = sample_indexed(input, mode, seed)? {
+        let content = serialize_rows(&headers, &rows, format, xml)?;
+        std::fs::write(output, content).context("Failed to write output file")?;
+        return Ok(());
+    }
+
+    let mut reader = csv::Reader::from_reader(get_reader(input)?);
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    let records = reader
+        .into_records()
+        .map(|record| Ok(record?.iter().map(String::from).collect()));
+    let rows = sample_rows(&headers, records, mode, seed)?;
+    let content = serialize_rows(&headers, &rows, format, xml)?;
+    std::fs::write(output, content).context("Failed to write output file")?;
+    Ok(())
+}
+
+fn sample_indexed(
+    input: &str,
+    mode: &SampleMode,
+    seed: Option<u64>,
+) -> anyhow::Result<Option<Table>> {
+    let Some(index) = (input != "-")
+        .then(|| CsvIndex::load_fresh(input))
+        .flatten()
+    else {
+        return Ok(None);
+    };
+    let total = index.count();
+    let rows: Vec<usize> = match mode {
+        // the same reservoir draws as an unindexed pass, so a seed picks the
+        // same rows with or without the index
+        SampleMode::Random { n } => {
+            let mut rng = rng(seed);
+            let mut reservoir = Reservoir::new(*n);
+            for i in 0..total {
+                reservoir.offer(&mut rng, (i, Vec::new()));
+            }
+            let mut rows: Vec<usize> = reservoir.rows.into_iter().map(|(i, _)| i).collect();
+            rows.sort_unstable();
+            rows
+        }
+        SampleMode::Tail(n) => (total.saturating_sub(*n)..total).collect(),
+        _ => return Ok(None),
+    };
+    read_indexed_rows(input, &index, &rows).map(Some)
+}
+
+fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+pub fn sample_rows(
+    headers: &[String],
+    records: impl Iterator<Item = anyhow::Result<Vec<String>>>,
+    mode: &SampleMode,
+    seed: Option<u64>,
+) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut rng = rng(seed);
+    let mut kept: Vec<Indexed> = match mode {
+        SampleMode::Head(n) => records
+            .take(*n)
+            .enumerate()
+            .map(|(i, r)| Ok((i, r?)))
+            .collect::<anyhow::Result<_>>()?,
+        SampleMode::Tail(n) => {
+            let mut tail = VecDeque::with_capacity(*n);
+            for (i, record) in records.enumerate() {
+                if tail.len() == *n {
+                    tail.pop_front();
+                }
+                if *n > 0 {
+                    tail.push_back((i, record?));
+                }
+            }
+            tail.into()
+        }
+        SampleMode::Random { n } => {
+            let mut reservoir = Reservoir::new(*n);
+            for (i, record) in records.enumerate() {
+                reservoir.offer(&mut rng, (i, record?));
+            }
+            reservoir.rows
+        }
+        SampleMode::Stratified { n, column } => {
+            let idx = headers
+                .iter()
+                .position(|h| h == column)
+                .with_context(|| format!("Column not found: {}", column))?;
+            stratified(&mut rng, records, idx, *n)?
+        }
+    };
+    kept.sort_by_key(|(i, _)| *i);
+    Ok(kept.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Algorithm R, every row seen so far is kept with the same probability
+struct Reservoir {
+    size: usize,
+    seen: usize,
+    rows: Vec<Indexed>,
+}
+
+impl Reservoir {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            seen: 0,
+            rows: Vec::with_capacity(size.min(1024)),
+        }
+    }
+
+    fn offer(&mut self, rng: &mut StdRng, row: Indexed) {
+        self.seen += 1;
+        if self.rows.len() < self.size {
+            self.rows.push(row);
+        } else {
+            let j = rng.gen_range(0..self.seen);
+            if j < self.size {
+                self.rows[j] = row;
+            }
+        }
+    }
+}
+
+/// One reservoir of `n` rows per group, then each group is cut down to its
+/// share of `n` using the largest remainder method
+fn stratified(
+    rng: &mut StdRng,
+    records: impl Iterator<Item = anyhow::Result<Vec<String>>>,
+    column: usize,
+    n: usize,
+) -> anyhow::Result<Vec<Indexed>> {
+    let mut groups: HashMap<String, Reservoir> = HashMap::new();
+    let mut total = 0;
+    for (i, record) in records.enumerate() {
+        let record = record?;
+        let key = record.get(column).cloned().unwrap_or_default();
+        groups
+            .entry(key)
+            .or_insert_with(|| Reservoir::new(n))
+            .offer(rng, (i, record));
+        total += 1;
+    }
+    if total == 0 {
+        return Ok(Vec::new());
+    }
+
+    // sort groups so the allocation does not depend on hash order
+    let mut groups: Vec<(String, Reservoir)> = groups.into_iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    let n = n.min(total);
+    let exact: Vec<f64> = groups
+        .iter()
+        .map(|(_, g)| n as f64 * g.seen as f64 / total as f64)
+        .collect();
+    let mut shares: Vec<usize> = exact.iter().map(|e| e.floor() as usize).collect();
+    let mut order: Vec<usize> = (0..groups.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ra = exact[a] - exact[a].floor();
+        let rb = exact[b] - exact[b].floor();
+        rb.total_cmp(&ra)
+    });
+    let missing = n - shares.iter().sum::<usize>();
+    for &g in order.iter().take(missing) {
+        shares[g] += 1;
+    }
+
+    let mut kept = Vec::with_capacity(n);
+    for ((_, group), share) in groups.into_iter().zip(shares) {
+        let mut rows = group.rows;
+        let picks = index::sample(rng, rows.len(), share.min(rows.len()));
+        let mut picks = picks.into_vec();
+        picks.sort_unstable_by(|a, b| b.cmp(a));
+        for pick in picks {
+            kept.push(rows.swap_remove(pick));
+        }
+    }
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(n: usize) -> impl Iterator<Item = anyhow::Result<Vec<String>>> {
+        (0..n).map(|i| {
+            let group = if i % 4 == 0 { "a" } else { "b" };
+            Ok(vec![i.to_string(), group.to_string()])
+        })
+    }
+
+    #[test]
+    fn test_sample_rows() -> anyhow::Result<()> {
+        let headers = vec!["id".to_string(), "group".to_string()];
+        let head = sample_rows(&headers, rows(100), &SampleMode::Head(3), None)?;
+        assert_eq!(
+            head.iter().map(|r| r[0].as_str()).collect::<Vec<_>>(),
+            ["0", "1", "2"]
+        );
+        let tail = sample_rows(&headers, rows(100), &SampleMode::Tail(2), None)?;
+        assert_eq!(
+            tail.iter().map(|r| r[0].as_str()).collect::<Vec<_>>(),
+            ["98", "99"]
+        );
+
+        let mode = SampleMode::Random { n: 10 };
+        let a = sample_rows(&headers, rows(1000), &mode, Some(42))?;
+        let b = sample_rows(&headers, rows(1000), &mode, Some(42))?;
+        assert_eq!(a.len(), 10);
+        assert_eq!(a, b);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_indexed_matches_unindexed() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-sample-index.csv");
+        std::fs::copy("./assets/juventus.csv", &input)?;
+        let input = input.to_str().unwrap();
+        let output = dir.join("rcli-sample-index.json");
+        let output = output.to_str().unwrap();
+        let mode = SampleMode::Random { n: 5 };
+        let xml = XmlOptions::default();
+
+        process_csv_sample(input, output, OutputFormat::Json, &mode, Some(9), &xml)?;
+        let unindexed = std::fs::read_to_string(output)?;
+        crate::process_csv_index(input)?;
+        assert!(sample_indexed(input, &mode, Some(9))?.is_some());
+        process_csv_sample(input, output, OutputFormat::Json, &mode, Some(9), &xml)?;
+        assert_eq!(std::fs::read_to_string(output)?, unindexed);
+
+        std::fs::remove_file(CsvIndex::path(input))?;
+        std::fs::remove_file(input)?;
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_stratified() -> anyhow::Result<()> {
+        let headers = vec!["id".to_string(), "group".to_string()];
+        let mode = SampleMode::Stratified {
+            n: 20,
+            column: "group".into(),
+        };
+        let sample = sample_rows(&headers, rows(1000), &mode, Some(7))?;
+        assert_eq!(sample.len(), 20);
+        assert_eq!(sample.iter().filter(|r| r[1] == "a").count(), 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeSet;
+
+use anyhow::Context;
+use convert_case::{Case, Casing};
+use csv::Reader;
+use serde::Serialize;
+use serde_json::json;
+
+use super::xml::unique_names;
+use crate::{cli::SchemaFormat, parse_date};
+
+/// date formats tried when inferring a date column
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%b %d, %Y", "%d/%m/%Y", "%m/%d/%Y"];
+
+#[derive(Debug, Serialize)]
+pub struct CsvSchema {
+    pub rows_sampled: usize,
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ColumnType,
+    pub nullable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_format: Option<String>,
+    #[serde(rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    pub enum_values: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Number,
+    Boolean,
+    Date,
+    String,
+}
+
+/// Read up to `sample` rows and write the inferred schema in `format`.
+/// Columns with at most `max_enum` distinct repeated values get enum
+/// candidates.
+pub fn process_csv_infer_schema(
+    input: &str,
+    sample: usize,
+    max_enum: usize,
+    format: SchemaFormat,
+) -> anyhow::Result<String> {
+    let mut reader = Reader::from_path(input).context("Failed to open input file")?;
+    let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+    let mut values: Vec<Vec<String>> = vec![Vec::new(); headers.len()];
+    let mut rows = 0;
+    for result in reader.records().take(sample) {
+        let record = result?;
+        for (column, field) in values.iter_mut().zip(record.iter()) {
+            column.push(field.to_string());
+        }
+        rows += 1;
+    }
+
+    let schema = CsvSchema {
+        rows_sampled: rows,
+        columns: headers
+            .iter()
+            .zip(values.iter())
+            .map(|(name, values)| infer_column(name, values, max_enum))
+            .collect(),
+    };
+    Ok(match format {
+        SchemaFormat::Yaml => serde_yaml::to_string(&schema)?,
+        SchemaFormat::JsonSchema => serde_json::to_string_pretty(&schema.to_json_schema())?,
+        SchemaFormat::Rust => schema.to_rust_struct("Record"),
+    })
+}
+
+pub fn infer_column(name: &str, values: &[String], max_enum: usize) -> ColumnSchema {
+    let present: Vec<&str> = values
+        .iter()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect();
+    let nullable = present.len() < values.len() || present.is_empty();
+    let numbers: Option<Vec<f64>> = present.iter().map(|v| v.parse().ok()).collect();
+    let date_format = DATE_FORMATS
+        .iter()
+        .find(|f| present.iter().all(|v| parse_date(v, f).is_ok()));
+
+    let kind = match () {
+        _ if present.is_empty() => ColumnType::String,
+        _ if present.iter().all(|v| v.parse::<i64>().is_ok()) => ColumnType::Integer,
+        _ if numbers.is_some() => ColumnType::Number,
+        _ if present.iter().all(|v| v.parse::<bool>().is_ok()) => ColumnType::Boolean,
+        _ if date_format.is_some() => ColumnType::Date,
+        _ => ColumnType::String,
+    };
+
+    let distinct: BTreeSet<&str> = present.iter().copied().collect();
+    let enum_values = match kind {
+        ColumnType::String if distinct.len() <= max_enum && distinct.len() < present.len() => {
+            distinct.iter().map(|v| v.to_string()).collect()
+        }
+        _ => Vec::new(),
+    };
+    let (min, max) = match (kind, numbers) {
+        (ColumnType::Integer | ColumnType::Number, Some(n)) => (
+            n.iter().copied().reduce(f64::min),
+            n.iter().copied().reduce(f64::max),
+        ),
+        _ => (None, None),
+    };
+
+    ColumnSchema {
+        name: name.to_string(),
+        kind,
+        nullable,
+        date_format: match kind {
+            ColumnType::Date => date_format.map(|f| f.to_string()),
+            _ => None,
+        },
+        enum_values,
+        min,
+        max,
+    }
+}
+
+impl CsvSchema {
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        let mut properties = serde_json::Map::new();
+        let mut required = Vec::new();
+        for col in &self.columns {
+            let kind = match col.kind {
+                ColumnType::Integer => "integer",
+                ColumnType::Number => "number",
+                ColumnType::Boolean => "boolean",
+                ColumnType::Date | ColumnType::String => "string",
+            };
+            let mut prop = match col.nullable {
+                true => json!({ "type": [kind, "null"] }),
+                false => json!({ "type": kind }),
+            };
+            if !col.enum_values.is_empty() {
+                prop["enum"] = json!(col.enum_values);
+            }
+            if let Some(min) = col.min {
+                prop["minimum"] = json!(min);
+            }
+            if let Some(max) = col.max {
+                prop["maximum"] = json!(max);
+            }
+            if let Some(format) = &col.date_format {
+                prop["x-date-format"] = json!(format);
+            }
+            properties.insert(col.name.clone(), prop);
+            if !col.nullable {
+                required.push(col.name.clone());
+            }
+        }
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Record",
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            }
+        })
+    }
+
+    /// Suggest a serde struct like [`crate::Record`] for the sampled columns
+    pub fn to_rust_struct(&self, name: &str) -> String {
+        let mut out = String::from("#[derive(Debug, Deserialize, Serialize)]\n");
+        out.push_str(&format!("pub struct {} {{\n", name));
+        let fields: Vec<String> = self
+            .columns
+            .iter()
+            .map(|col| {
+                let mut field = col.name.to_case(Case::Snake);
+                if field.is_empty() || field.starts_with(|c: char| c.is_ascii_digit()) {
+                    field.insert(0, '_');
+                }
+                // these cannot be raw identifiers
+                if matches!(field.as_str(), "self" | "super" | "crate" | "_") {
+                    field.push('_');
+                }
+                field
+            })
+            .collect();
+        // `Kit Number` and `kit_number` would otherwise become the same field
+        for (col, mut field) in self.columns.iter().zip(unique_names(fields)) {
+            if field != col.name {
+                out.push_str(&format!("    #[serde(rename = {:?})]\n", col.name));
+            }
+            if RUST_KEYWORDS.contains(&field.as_str()) {
+                field.insert_str(0, "r#");
+            }
+            let ty = rust_type(col);
+            let ty = match col.nullable {
+                true => format!("Option<{}>", ty),
+                false => ty.to_string(),
+            };
+            out.push_str(&format!("    {}: {},\n", field, ty));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Strict and reserved keywords of the 2024 edition, written as `r#name`
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+fn rust_type(col: &ColumnSchema) -> &'static str {
+    match (col.kind, col.min, col.max) {
+        (ColumnType::Integer, Some(min), Some(max)) if min >= 0.0 => match max {
+            m if m <= u8::MAX as f64 => "u8",
+            m if m <= u16::MAX as f64 => "u16",
+            m if m <= u32::MAX as f64 => "u32",
+            _ => "u64",
+        },
+        (ColumnType::Integer, Some(min), Some(max))
+            if min >= i32::MIN as f64 && max <= i32::MAX as f64 =>
+        {
+            "i32"
+        }
+        (ColumnType::Integer, ..) => "i64",
+        (ColumnType::Number, ..) => "f64",
+        (ColumnType::Boolean, ..) => "bool",
+        (ColumnType::Date | ColumnType::String, ..) => "String",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_column() {
+        let values = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let kit = infer_column("Kit Number", &values(&["1", "37", "77"]), 10);
+        assert_eq!(kit.kind, ColumnType::Integer);
+        assert_eq!(rust_type(&kit), "u8");
+
+        let dob = infer_column("DOB", &values(&["Apr 18, 1990 (29)", ""]), 10);
+        assert_eq!(dob.kind, ColumnType::Date);
+        assert!(dob.nullable);
+
+        let pos = infer_column(
+            "Position",
+            &values(&["Goalkeeper", "Goalkeeper", "Defender"]),
+            10,
+        );
+        assert_eq!(pos.kind, ColumnType::String);
+        assert_eq!(pos.enum_values, vec!["Defender", "Goalkeeper"]);
+    }
+
+    #[test]
+    fn test_infer_schema_rust() -> anyhow::Result<()> {
+        let rust = process_csv_infer_schema("./assets/juventus.csv", 100, 10, SchemaFormat::Rust)?;
+        assert!(rust.contains("#[serde(rename = \"Kit Number\")]\n    kit_number: u8,"));
+        assert!(rust.contains("    #[serde(rename = \"Name\")]\n    name: String,"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rust_struct_keywords() {
+        let columns = ["type", "Match", "self", "fn"]
+            .iter()
+            .map(|name| infer_column(name, &["x".to_string()], 10))
+            .collect();
+        let schema = CsvSchema {
+            rows_sampled: 1,
+            columns,
+        };
+        let rust = schema.to_rust_struct("Row");
+        assert!(rust.contains("\n    r#type: String,"), "{}", rust);
+        assert!(rust.contains("rename = \"Match\")]\n    r#match: String,"));
+        assert!(rust.contains("rename = \"self\")]\n    self_: String,"));
+        assert!(rust.contains("\n    r#fn: String,"));
+    }
+
+    #[test]
+    fn test_rust_struct_unique_fields() {
+        let columns = ["Kit Number", "kit_number", "kit_number_2"]
+            .iter()
+            .map(|name| infer_column(name, &["x".to_string()], 10))
+            .collect();
+        let schema = CsvSchema {
+            rows_sampled: 1,
+            columns,
+        };
+        let rust = schema.to_rust_struct("Row");
+        assert!(rust.contains("rename = \"Kit Number\")]\n    kit_number: String,"));
+        assert!(rust.contains("rename = \"kit_number\")]\n    kit_number_3: String,"));
+        assert!(rust.contains("\n    kit_number_2: String,"), "{}", rust);
+    }
+}
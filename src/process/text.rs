@@ -4,7 +4,10 @@ use anyhow::Result;
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 
-use crate::{cli::TextSignFormat, process::gen_pass};
+use crate::{
+    cli::TextSignFormat,
+    process::{PassSpec, gen_pass},
+};
 trait TextSigner {
     fn sign(&self, input: &mut dyn Read) -> Result<Vec<u8>>;
 }
@@ -24,7 +27,7 @@ impl Blake3 {
     }
 
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = gen_pass(32, true, true, true, true)?;
+        let key = gen_pass(&PassSpec::new(32))?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
         Ok(map)
@@ -0,0 +1,187 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::{CryptoRng, Rng, RngCore, seq::SliceRandom};
+use uuid::{Builder, Uuid};
+
+use crate::cli::SecretEncoding;
+
+/// The nanoid default: URL-safe, 64 symbols, 6 bits per character.
+pub const NANOID_ALPHABET: &str =
+    "_-0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Crockford's base32, as used by ULIDs.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Random (version 4) UUID built from our own random bytes rather than uuid's rng.
+pub fn gen_uuid_v4<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Uuid {
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    Builder::from_random_bytes(bytes).into_uuid()
+}
+
+/// Timestamp and random part of the last time ordered id, so ids made within one
+/// millisecond still sort in the order they were made. As in the ULID spec's monotonic
+/// mode, a repeated (or earlier) millisecond reuses the last random part plus one.
+#[derive(Debug, Default)]
+pub struct Monotonic {
+    last: Option<(u64, u128)>,
+}
+
+impl Monotonic {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The timestamp and `bits` random bits for the next id.
+    fn next<R: RngCore + CryptoRng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        millis: u64,
+        bits: u32,
+    ) -> (u64, u128) {
+        let max = (1u128 << bits) - 1;
+        let next = match self.last {
+            Some((last, random)) if millis <= last && random < max => (last, random + 1),
+            // the random part ran out, borrow the next millisecond
+            Some((last, _)) if millis <= last => (last + 1, random_bits(rng, bits)),
+            _ => (millis, random_bits(rng, bits)),
+        };
+        self.last = Some(next);
+        next
+    }
+}
+
+fn random_bits<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, bits: u32) -> u128 {
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    u128::from_be_bytes(bytes) >> (128 - bits)
+}
+
+/// Time ordered (version 7) UUID for `millis` since the Unix epoch, monotonic across
+/// calls that share `clock`.
+pub fn gen_uuid_v7<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    clock: &mut Monotonic,
+    millis: u64,
+) -> Uuid {
+    // 74 random bits: 12 in rand_a after the version, 62 in rand_b after the variant
+    let (millis, random) = clock.next(rng, millis, 74);
+    let mut bytes = [0u8; 10];
+    bytes[..2].copy_from_slice(&((random >> 62) as u16).to_be_bytes());
+    bytes[2..].copy_from_slice(&((random as u64) & ((1 << 62) - 1)).to_be_bytes());
+    Builder::from_unix_timestamp_millis(millis, &bytes).into_uuid()
+}
+
+/// ULID: a 48-bit millisecond timestamp and 80 random bits, as 26 Crockford base32
+/// characters, monotonic across calls that share `clock`.
+pub fn gen_ulid<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    clock: &mut Monotonic,
+    millis: u64,
+) -> String {
+    let (millis, random) = clock.next(rng, millis, 80);
+    let value = (u128::from(millis & 0xFFFF_FFFF_FFFF) << 80) | random;
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+pub fn gen_nanoid<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    len: usize,
+    alphabet: &str,
+) -> anyhow::Result<String> {
+    let mut symbols: Vec<char> = Vec::new();
+    for c in alphabet.chars() {
+        if !symbols.contains(&c) {
+            symbols.push(c);
+        }
+    }
+    anyhow::ensure!(
+        symbols.len() >= 2,
+        "The nanoid alphabet needs at least two distinct characters"
+    );
+    anyhow::ensure!(len > 0, "The nanoid length must be at least 1");
+    Ok((0..len)
+        .map(|_| *symbols.choose(rng).expect("alphabet is not empty"))
+        .collect())
+}
+
+pub fn gen_secret<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    bytes: usize,
+    encoding: SecretEncoding,
+) -> String {
+    let mut buf = vec![0u8; bytes];
+    rng.fill(buf.as_mut_slice());
+    match encoding {
+        SecretEncoding::Hex => hex::encode(buf),
+        SecretEncoding::Base64url => URL_SAFE_NO_PAD.encode(buf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+    use uuid::Version;
+
+    use super::*;
+
+    #[test]
+    fn test_gen_ids() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(gen_uuid_v4(&mut rng).get_version(), Some(Version::Random));
+        let v7 = gen_uuid_v7(&mut rng, &mut Monotonic::new(), 1_700_000_000_000);
+        assert_eq!(v7.get_version(), Some(Version::SortRand));
+        assert_eq!(v7.get_timestamp().unwrap().to_unix(), (1_700_000_000, 0));
+
+        let ulid = gen_ulid(&mut rng, &mut Monotonic::new(), 1_700_000_000_000);
+        assert_eq!(ulid.len(), 26);
+        assert!(ulid.starts_with("01HF"));
+        assert!(gen_ulid(&mut rng, &mut Monotonic::new(), 1_700_000_000_001) > ulid);
+
+        let id = gen_nanoid(&mut rng, 21, NANOID_ALPHABET).unwrap();
+        assert_eq!(id.len(), 21);
+        assert!(gen_nanoid(&mut rng, 21, "aaa").is_err());
+
+        assert_eq!(gen_secret(&mut rng, 32, SecretEncoding::Hex).len(), 64);
+        assert_eq!(
+            gen_secret(&mut rng, 32, SecretEncoding::Base64url).len(),
+            43
+        );
+    }
+
+    #[test]
+    fn test_time_ordered_ids_are_monotonic() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut clock = Monotonic::new();
+        // the same millisecond, then a clock that steps backwards
+        let millis = [1_700_000_000_000; 500]
+            .into_iter()
+            .chain([1_699_999_999_999; 500]);
+        let ulids: Vec<String> = millis
+            .clone()
+            .map(|ms| gen_ulid(&mut rng, &mut clock, ms))
+            .collect();
+        assert!(ulids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ulids.iter().all(|id| id.starts_with("01HF")));
+
+        let mut clock = Monotonic::new();
+        let uuids: Vec<Uuid> = millis
+            .map(|ms| gen_uuid_v7(&mut rng, &mut clock, ms))
+            .collect();
+        assert!(uuids.windows(2).all(|w| w[0] < w[1]));
+        assert!(
+            uuids
+                .iter()
+                .all(|id| id.get_version() == Some(Version::SortRand))
+        );
+
+        // an exhausted random part moves on to the next millisecond
+        let mut clock = Monotonic {
+            last: Some((1_700_000_000_000, (1 << 80) - 1)),
+        };
+        let ulid = gen_ulid(&mut rng, &mut clock, 1_700_000_000_000);
+        assert_eq!(clock.last.unwrap().0, 1_700_000_000_001);
+        assert!(ulid > gen_ulid(&mut rng, &mut Monotonic::new(), 1_700_000_000_000));
+    }
+}
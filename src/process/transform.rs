@@ -0,0 +1,106 @@
+use anyhow::Context;
+use chrono::{Local, NaiveDate};
+use csv::StringRecord;
+
+use crate::{CsvConvertOptions, Expr, age_at, format_date, parse_date};
+
+/// Per row changes requested on top of a plain csv conversion
+pub struct RowTransform<'a> {
+    opts: &'a CsvConvertOptions,
+    headers: StringRecord,
+    /// number of columns in the input
+    input_len: usize,
+    /// indexes of the date columns in the input
+    date_columns: Vec<usize>,
+    /// computed column expressions bound to the columns before them
+    exprs: Vec<Expr>,
+    today: NaiveDate,
+}
+
+impl<'a> RowTransform<'a> {
+    pub fn new(headers: &StringRecord, opts: &'a CsvConvertOptions) -> anyhow::Result<Self> {
+        let date_columns = opts
+            .dates
+            .columns
+            .iter()
+            .map(|col| column_index(headers, col))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut out = headers.clone();
+        if let Some(age) = &opts.dates.age_column {
+            anyhow::ensure!(
+                !date_columns.is_empty(),
+                "An age column needs a date column"
+            );
+            out.push_field(age);
+        }
+        let mut exprs = Vec::with_capacity(opts.computed.len());
+        for col in &opts.computed {
+            let names: Vec<&str> = out.iter().collect();
+            let expr = col
+                .expr
+                .clone()
+                .bind(&names)
+                .with_context(|| format!("Invalid expression for column {}", col.name))?;
+            exprs.push(expr);
+            out.push_field(&col.name);
+        }
+        Ok(Self {
+            opts,
+            headers: out,
+            input_len: headers.len(),
+            date_columns,
+            exprs,
+            today: Local::now().date_naive(),
+        })
+    }
+
+    /// headers of the transformed rows
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    pub fn input_len(&self) -> usize {
+        self.input_len
+    }
+
+    pub fn apply(&self, record: StringRecord) -> anyhow::Result<StringRecord> {
+        if self.date_columns.is_empty() && self.exprs.is_empty() {
+            return Ok(record);
+        }
+        let mut fields: Vec<String> = record.iter().map(String::from).collect();
+        self.apply_dates(&mut fields)?;
+        for expr in &self.exprs {
+            let value = expr.eval(&fields)?;
+            fields.push(value.to_string());
+        }
+        Ok(StringRecord::from(fields))
+    }
+
+    fn apply_dates(&self, fields: &mut Vec<String>) -> anyhow::Result<()> {
+        let dates = &self.opts.dates;
+        let mut first_date = None;
+        for (n, &idx) in self.date_columns.iter().enumerate() {
+            let Some(field) = fields.get_mut(idx).filter(|f| !f.trim().is_empty()) else {
+                continue;
+            };
+            let date = parse_date(field, &dates.input_format)?;
+            *field = format_date(date, dates.output_format())?;
+            if n == 0 {
+                first_date = Some(date);
+            }
+        }
+        if dates.age_column.is_some() {
+            let age = first_date.map(|d| age_at(d, self.today).to_string());
+            fields.push(age.unwrap_or_default());
+        }
+        Ok(())
+    }
+}
+
+fn column_index(headers: &StringRecord, name: &str) -> anyhow::Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == name)
+        .with_context(|| format!("Column not found: {}", name))
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+};
+
+use anyhow::Context;
+use quick_xml::{
+    XmlVersion,
+    escape::{escape, unescape},
+    events::Event,
+};
+
+use crate::{cli::OutputFormat, serialize_rows};
+
+/// How csv rows are laid out when written as XML
+#[derive(Debug, Clone)]
+pub struct XmlOptions {
+    pub root: String,
+    pub row: String,
+    /// write columns as attributes of the row element instead of child
+    /// elements
+    pub attributes: bool,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        Self {
+            root: "rows".into(),
+            row: "row".into(),
+            attributes: false,
+        }
+    }
+}
+
+/// Buffered XML writer, one row element per csv record
+pub struct XmlWriter {
+    buf: String,
+    tags: Vec<String>,
+    row: String,
+    root: String,
+    attributes: bool,
+}
+
+impl XmlWriter {
+    pub fn new<S: AsRef<str>>(headers: &[S], opts: &XmlOptions) -> Self {
+        let mut wtr = Self::fragment(headers, opts);
+        wtr.buf = Self::prolog(opts);
+        wtr
+    }
+
+    /// Writer for rows only, the document is opened by [`XmlWriter::prolog`]
+    /// and closed by [`XmlWriter::epilogue`]
+    pub fn fragment<S: AsRef<str>>(headers: &[S], opts: &XmlOptions) -> Self {
+        Self {
+            buf: String::new(),
+            tags: sanitize_headers(headers),
+            row: sanitize_tag(&opts.row),
+            root: sanitize_tag(&opts.root),
+            attributes: opts.attributes,
+        }
+    }
+
+    pub fn prolog(opts: &XmlOptions) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{}>\n",
+            sanitize_tag(&opts.root)
+        )
+    }
+
+    pub fn epilogue(opts: &XmlOptions) -> String {
+        format!("</{}>\n", sanitize_tag(&opts.root))
+    }
+
+    pub fn write_row<'a>(&mut self, fields: impl IntoIterator<Item = &'a str>) {
+        let fields = self.tags.iter().zip(fields);
+        if self.attributes {
+            self.buf.push_str(&format!("  <{}", self.row));
+            for (tag, value) in fields {
+                self.buf
+                    .push_str(&format!(" {}=\"{}\"", tag, escape(strip_invalid(value))));
+            }
+            self.buf.push_str("/>\n");
+        } else {
+            self.buf.push_str(&format!("  <{}>\n", self.row));
+            for (tag, value) in fields {
+                self.buf.push_str(&format!(
+                    "    <{0}>{1}</{0}>\n",
+                    tag,
+                    escape(strip_invalid(value))
+                ));
+            }
+            self.buf.push_str(&format!("  </{}>\n", self.row));
+        }
+    }
+
+    pub fn into_inner(mut self) -> Vec<u8> {
+        self.buf.push_str(&format!("</{}>\n", self.root));
+        self.buf.into_bytes()
+    }
+
+    pub fn into_fragment(self) -> Vec<u8> {
+        self.buf.into_bytes()
+    }
+}
+
+/// Turn an arbitrary header into a valid XML element name, e.g. `Kit Number`
+/// becomes `Kit_Number`
+pub fn sanitize_tag(name: &str) -> String {
+    let mut tag: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let bad_start = tag
+        .chars()
+        .next()
+        .is_none_or(|c| !(c.is_alphabetic() || c == '_'));
+    // names starting with `xml` are reserved by the spec
+    if bad_start || tag.to_lowercase().starts_with("xml") {
+        tag.insert(0, '_');
+    }
+    tag
+}
+
+/// Sanitize every header, suffixing duplicates so each column keeps its own
+/// element
+fn sanitize_headers<S: AsRef<str>>(headers: &[S]) -> Vec<String> {
+    unique_names(headers.iter().map(|h| sanitize_tag(h.as_ref())).collect())
+}
+
+/// Suffix repeated names with `_2`, `_3`... skipping any suffix that is already
+/// taken, so `a`, `a`, `a_2` becomes `a`, `a_3`, `a_2`
+pub(crate) fn unique_names(names: Vec<String>) -> Vec<String> {
+    let mut taken: HashSet<String> = names.iter().cloned().collect();
+    let mut kept = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            if kept.insert(name.clone()) {
+                return name;
+            }
+            (2..)
+                .map(|n| format!("{}_{}", name, n))
+                .find(|candidate| taken.insert(candidate.clone()))
+                .expect("an unused suffix exists")
+        })
+        .collect()
+}
+
+/// XML 1.0 does not allow most control characters, even escaped
+fn strip_invalid(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+/// Read `<root><row>...</row></root>` documents into headers and rows.
+///
+/// Attributes of a row element and its child elements both become columns,
+/// headers are ordered by first appearance.
+pub fn read_xml(input: &str) -> anyhow::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let mut reader = quick_xml::Reader::from_str(input);
+    let mut headers: Vec<String> = Vec::new();
+    let mut rows: Vec<HashMap<String, String>> = Vec::new();
+    let mut row: HashMap<String, String> = HashMap::new();
+    // column of the child element being read
+    let mut field: Option<String> = None;
+    let mut depth = 0;
+
+    loop {
+        match reader.read_event().context("Failed to parse XML")? {
+            Event::Start(e) => {
+                depth += 1;
+                match depth {
+                    2 => read_attributes(&e, &mut headers, &mut row)?,
+                    3 => {
+                        let name = local_name(&e);
+                        push_header(&mut headers, &name);
+                        row.entry(name.clone()).or_default();
+                        field = Some(name);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(e) => match depth + 1 {
+                2 => {
+                    read_attributes(&e, &mut headers, &mut row)?;
+                    rows.push(std::mem::take(&mut row));
+                }
+                3 => {
+                    let name = local_name(&e);
+                    push_header(&mut headers, &name);
+                    row.entry(name).or_default();
+                }
+                _ => {}
+            },
+            Event::End(_) => {
+                match depth {
+                    2 => rows.push(std::mem::take(&mut row)),
+                    3 => field = None,
+                    _ => {}
+                }
+                depth -= 1;
+            }
+            Event::Text(t) => push_text(&mut row, &field, &t.xml10_content()),
+            Event::CData(t) => push_text(&mut row, &field, &t.xml10_content()),
+            Event::GeneralRef(r) => {
+                let reference = format!("&{};", r.xml10_content());
+                push_text(&mut row, &field, &unescape(&reference)?);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let rows = rows
+        .into_iter()
+        .map(|mut row| {
+            headers
+                .iter()
+                .map(|h| row.remove(h).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    Ok((headers, rows))
+}
+
+/// Convert an XML document produced by [`XmlWriter`] (or shaped like it) to
+/// another format
+pub fn process_xml(
+    reader: &mut dyn Read,
+    output: &str,
+    format: OutputFormat,
+    opts: &XmlOptions,
+) -> anyhow::Result<()> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let (headers, rows) = read_xml(&input)?;
+
+    let content = serialize_rows(&headers, &rows, format, opts)?;
+    std::fs::write(output, content).context("Failed to write output file")?;
+    Ok(())
+}
+
+fn local_name(e: &quick_xml::events::BytesStart) -> String {
+    e.local_name().as_ref().to_string()
+}
+
+fn push_header(headers: &mut Vec<String>, name: &str) {
+    if !headers.iter().any(|h| h == name) {
+        headers.push(name.to_string());
+    }
+}
+
+fn push_text(row: &mut HashMap<String, String>, field: &Option<String>, text: &str) {
+    if let Some(value) = field.as_ref().and_then(|f| row.get_mut(f)) {
+        value.push_str(text);
+    }
+}
+
+fn read_attributes(
+    e: &quick_xml::events::BytesStart,
+    headers: &mut Vec<String>,
+    row: &mut HashMap<String, String>,
+) -> anyhow::Result<()> {
+    for attr in e.attributes() {
+        let attr = attr?;
+        let name = attr.key.local_name().as_ref().to_string();
+        push_header(headers, &name);
+        row.insert(
+            name,
+            attr.normalized_value(XmlVersion::Implicit1_0)?.to_string(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_tag() {
+        assert_eq!(sanitize_tag("Kit Number"), "Kit_Number");
+        assert_eq!(sanitize_tag("1st"), "_1st");
+        assert_eq!(sanitize_tag("xmlData"), "_xmlData");
+        assert_eq!(sanitize_tag(""), "_");
+        assert_eq!(sanitize_headers(&["a b", "a_b"]), vec!["a_b", "a_b_2"]);
+        assert_eq!(
+            sanitize_headers(&["a", "a", "a_2", "a"]),
+            vec!["a", "a_3", "a_2", "a_4"]
+        );
+    }
+
+    #[test]
+    fn test_xml_round_trip() -> anyhow::Result<()> {
+        let headers = ["Name", "Kit Number"];
+        for attributes in [false, true] {
+            let opts = XmlOptions {
+                attributes,
+                ..Default::default()
+            };
+            let mut wtr = XmlWriter::new(&headers, &opts);
+            wtr.write_row(["Tom & <Jerry>", "1"]);
+            wtr.write_row(["\"Quoted\"", "2"]);
+            let xml = String::from_utf8(wtr.into_inner())?;
+            assert!(xml.contains("Tom &amp; &lt;Jerry&gt;"));
+
+            let (headers, rows) = read_xml(&xml)?;
+            assert_eq!(headers, vec!["Name", "Kit_Number"]);
+            assert_eq!(rows[0], vec!["Tom & <Jerry>", "1"]);
+            assert_eq!(rows[1], vec!["\"Quoted\"", "2"]);
+        }
+        Ok(())
+    }
+}